@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE `message_revisions`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`message_id` INT8 NOT NULL REFERENCES `messages`(`id`),
+	`revision` INTEGER NOT NULL,
+	`content` TEXT NOT NULL,
+	`edited_at` TIMESTAMP NOT NULL,
+	UNIQUE(`message_id`, `revision`)
+);
+
+-- Every message known so far becomes its own first revision, dated by its snowflake.
+INSERT INTO `message_revisions`(`message_id`, `revision`, `content`, `edited_at`)
+SELECT `id`, 1, `content`, datetime(((`id` >> 22) + 1420070400000) / 1000, 'unixepoch')
+FROM `messages`;
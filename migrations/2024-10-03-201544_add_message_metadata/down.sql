@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `messages` DROP COLUMN `flags`;
+ALTER TABLE `messages` DROP COLUMN `reply_to`;
+ALTER TABLE `messages` DROP COLUMN `edited_at`;
+ALTER TABLE `messages` DROP COLUMN `created_at`;
+ALTER TABLE `messages` DROP COLUMN `channel_id`;
+ALTER TABLE `messages` DROP COLUMN `guild_id`;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+ALTER TABLE `messages` ADD COLUMN `guild_id` INT8;
+ALTER TABLE `messages` ADD COLUMN `channel_id` INT8;
+ALTER TABLE `messages` ADD COLUMN `created_at` TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE `messages` ADD COLUMN `edited_at` TIMESTAMP;
+ALTER TABLE `messages` ADD COLUMN `reply_to` INT8;
+ALTER TABLE `messages` ADD COLUMN `flags` INT8 NOT NULL DEFAULT 0;
+
+-- A snowflake carries its creation time (milliseconds since the Discord epoch) in its upper bits.
+UPDATE `messages`
+SET `created_at` = datetime(((`id` >> 22) + 1420070400000) / 1000, 'unixepoch');
+
+-- The latest stored revision is the best guess for when a known message was last edited.
+UPDATE `messages`
+SET `edited_at` = (
+	SELECT MAX(`edited_at`) FROM `message_revisions`
+	WHERE `message_revisions`.`message_id` = `messages`.`id` AND `revision` > 1
+);
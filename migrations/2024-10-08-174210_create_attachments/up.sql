@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE `attachments`(
+	`id` INT8 NOT NULL PRIMARY KEY,
+	`message_id` INT8 NOT NULL REFERENCES `messages`(`id`),
+	`filename` TEXT NOT NULL,
+	`content_type` TEXT,
+	`size` INT8 NOT NULL,
+	`url` TEXT NOT NULL,
+	`hash` TEXT
+);
+
+CREATE INDEX `attachments_message_id` ON `attachments`(`message_id`);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `attachments` DROP COLUMN `removed_at`;
+ALTER TABLE `messages` DROP COLUMN `pinned`;
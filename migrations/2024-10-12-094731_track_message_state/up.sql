@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE `messages` ADD COLUMN `pinned` BOOL NOT NULL DEFAULT FALSE;
+ALTER TABLE `attachments` ADD COLUMN `removed_at` TIMESTAMP;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE `guild_config`(
+	`guild_id` INT8 NOT NULL PRIMARY KEY,
+	`log_channel` INT8
+);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE `log_routes`(
+	`guild_id` INT8 NOT NULL,
+	`category` TEXT NOT NULL,
+	`channel_id` INT8 NOT NULL,
+	PRIMARY KEY (`guild_id`, `category`)
+);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE `ignore_rules`(
+	`guild_id` INT8 NOT NULL,
+	`kind` TEXT NOT NULL,
+	`target_id` INT8 NOT NULL,
+	PRIMARY KEY (`guild_id`, `kind`, `target_id`)
+);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE `guild_config` ADD COLUMN `notify_ghost_pings` BOOL NOT NULL DEFAULT FALSE;
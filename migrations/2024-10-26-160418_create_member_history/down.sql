@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `guild_config` DROP COLUMN `new_account_days`;
+DROP TABLE IF EXISTS `member_history`;
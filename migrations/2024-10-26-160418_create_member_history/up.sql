@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE `member_history`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`guild_id` INT8 NOT NULL,
+	`user_id` INT8 NOT NULL,
+	`joined_at` TIMESTAMP,
+	`left_at` TIMESTAMP
+);
+
+CREATE INDEX `member_history_guild_user` ON `member_history`(`guild_id`, `user_id`);
+
+ALTER TABLE `guild_config` ADD COLUMN `new_account_days` INTEGER NOT NULL DEFAULT 7;
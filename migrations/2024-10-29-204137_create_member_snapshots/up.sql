@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE `member_snapshots`(
+	`guild_id` INT8 NOT NULL,
+	`user_id` INT8 NOT NULL,
+	`nick` TEXT,
+	`avatar` TEXT,
+	`roles` TEXT NOT NULL,
+	`timeout_until` TIMESTAMP,
+	PRIMARY KEY (`guild_id`, `user_id`)
+);
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE `moderation_actions`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`guild_id` INT8 NOT NULL,
+	`user_id` INT8 NOT NULL,
+	`moderator_id` INT8,
+	`kind` TEXT NOT NULL,
+	`reason` TEXT,
+	`created_at` TIMESTAMP NOT NULL
+);
+
+CREATE INDEX `moderation_actions_guild_user` ON `moderation_actions`(`guild_id`, `user_id`);
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE `voice_sessions`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`guild_id` INT8 NOT NULL,
+	`user_id` INT8 NOT NULL,
+	`channel_id` INT8 NOT NULL,
+	`joined_at` TIMESTAMP NOT NULL,
+	`left_at` TIMESTAMP
+);
+
+CREATE INDEX `voice_sessions_guild_user` ON `voice_sessions`(`guild_id`, `user_id`);
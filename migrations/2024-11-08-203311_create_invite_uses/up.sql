@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE `invite_uses`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`guild_id` INT8 NOT NULL,
+	`user_id` INT8 NOT NULL,
+	`code` TEXT NOT NULL,
+	`inviter_id` INT8,
+	`used_at` TIMESTAMP NOT NULL
+);
+
+CREATE INDEX `invite_uses_guild` ON `invite_uses`(`guild_id`);
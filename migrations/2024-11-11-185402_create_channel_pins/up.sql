@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE `channel_pins`(
+	`channel_id` INT8 NOT NULL PRIMARY KEY,
+	`message_ids` TEXT NOT NULL
+);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+CREATE INDEX `messages_channel_id` ON `messages`(`channel_id`);
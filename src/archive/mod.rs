@@ -0,0 +1,118 @@
+use crate::error::DoomResult;
+use crate::util::env::var_or;
+use crate::Error;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Discord's default upload limit for bots, which caps how much can be re-uploaded at once.
+pub const UPLOAD_LIMIT: u64 = 10 * 1024 * 1024;
+
+const DEFAULT_MAX_SIZE: u64 = 25 * 1024 * 1024;
+const DEFAULT_CONTENT_TYPES: &str = "image/*,video/*,audio/*,text/*,application/pdf";
+
+pub struct ArchiveConfig {
+    pub directory: PathBuf,
+    pub max_size: u64,
+    /// MIME types that are archived, either exact (`image/png`), by family (`image/*`) or `*/*`.
+    pub content_types: Vec<String>,
+}
+
+impl ArchiveConfig {
+    pub fn from_env() -> DoomResult<Self> {
+        let directory: String = var_or("ATTACHMENT_DIRECTORY", "attachments".to_string())?;
+        let max_size = var_or("ATTACHMENT_MAX_SIZE", DEFAULT_MAX_SIZE)?;
+        let content_types = var_or(
+            "ATTACHMENT_CONTENT_TYPES",
+            DEFAULT_CONTENT_TYPES.to_string(),
+        )?
+        .split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+        Ok(ArchiveConfig {
+            directory: directory.into(),
+            max_size,
+            content_types,
+        })
+    }
+
+    pub fn allows(&self, content_type: Option<&str>, size: u64) -> bool {
+        if size > self.max_size {
+            return false;
+        }
+        let allows_any = self.content_types.iter().any(|t| t == "*/*");
+        let Some(content_type) = content_type else {
+            return allows_any;
+        };
+        // Strip parameters such as `; charset=utf-8`
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let family = essence.split('/').next().unwrap_or_default();
+        allows_any
+            || self
+                .content_types
+                .iter()
+                .any(|allowed| *allowed == essence || allowed.strip_suffix("/*") == Some(family))
+    }
+}
+
+/// Content-addressed file store for attachments, so they outlive their Discord CDN links.
+pub struct AttachmentArchive {
+    config: ArchiveConfig,
+    client: reqwest::Client,
+}
+
+impl AttachmentArchive {
+    pub fn new(config: ArchiveConfig) -> Self {
+        AttachmentArchive {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn path_of(&self, hash: &str) -> PathBuf {
+        self.config.directory.join(&hash[..2]).join(hash)
+    }
+
+    /// Downloads and stores the file behind `url`, returning its hash.
+    /// Files rejected by the configured size cap or content types are skipped with `Ok(None)`.
+    pub async fn archive(
+        &self,
+        url: &str,
+        content_type: Option<&str>,
+        size: u64,
+    ) -> Result<Option<String>, Error> {
+        if !self.config.allows(content_type, size) {
+            return Ok(None);
+        }
+
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        let mut bytes = Vec::with_capacity(size as usize);
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            // The announced size can't be trusted, so the cap is enforced while downloading.
+            if bytes.len() as u64 > self.config.max_size {
+                return Ok(None);
+            }
+        }
+
+        let hash = format!("{:x}", Sha256::digest(&bytes));
+        let path = self.path_of(&hash);
+        if !fs::try_exists(&path).await? {
+            fs::create_dir_all(&self.config.directory.join(&hash[..2])).await?;
+            let partial = path.with_extension("part");
+            fs::write(&partial, &bytes).await?;
+            fs::rename(&partial, &path).await?;
+        }
+        Ok(Some(hash))
+    }
+
+    pub async fn load(&self, hash: &str) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(self.path_of(hash)).await?)
+    }
+}
@@ -0,0 +1,159 @@
+use crate::error::DoomResult;
+use chrono::{NaiveDateTime, TimeDelta};
+use poise::serenity_prelude::audit_log::{Action, AuditLogEntry, MessageAction};
+use poise::serenity_prelude::{ChannelId, GuildId, Http, UserId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Discord writes the audit log entry around the time it dispatches the event, so lookups wait
+/// for a moment to not miss it.
+pub const AUDIT_LOG_DELAY: Duration = Duration::from_millis(1500);
+/// How old a freshly seen entry may be to still be attributed to an event.
+const ATTRIBUTION_WINDOW: TimeDelta = TimeDelta::seconds(15);
+/// How many of the latest entries are looked at.
+const LOOKUP_LIMIT: u8 = 25;
+
+/// An audit log entry, reduced to what events get attributed with.
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub user_id: UserId,
+    pub target_id: Option<u64>,
+    pub reason: Option<String>,
+    pub channel_id: Option<ChannelId>,
+    /// How many events Discord combined into the entry, i.e. deletions of the same author.
+    pub count: u64,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<&AuditLogEntry> for AuditEntry {
+    fn from(entry: &AuditLogEntry) -> Self {
+        AuditEntry {
+            id: entry.id.get(),
+            user_id: entry.user_id,
+            target_id: entry.target_id.map(|id| id.get()),
+            reason: entry.reason.clone(),
+            channel_id: entry.options.as_ref().and_then(|o| o.channel_id),
+            count: entry.options.as_ref().and_then(|o| o.count).unwrap_or(1),
+            created_at: entry.id.created_at().naive_utc(),
+        }
+    }
+}
+
+/// Where audit log entries come from, which is Discord apart from the tests.
+pub trait AuditSource {
+    /// The latest entries of an action in the guild, newest first.
+    fn entries(
+        &self,
+        guild_id: GuildId,
+        action: Action,
+        limit: u8,
+    ) -> impl Future<Output = DoomResult<Vec<AuditEntry>>> + Send;
+}
+
+impl AuditSource for Http {
+    async fn entries(
+        &self,
+        guild_id: GuildId,
+        action: Action,
+        limit: u8,
+    ) -> DoomResult<Vec<AuditEntry>> {
+        let logs = guild_id
+            .audit_logs(self, Some(action), None, None, Some(limit))
+            .await?;
+        Ok(logs.entries.iter().map(AuditEntry::from).collect())
+    }
+}
+
+/// Attributes a deletion to the first entry about the author in the channel which has a
+/// deletion left that no earlier event claimed. Discord adds repeated deletions of the same
+/// author to the existing entry, so those are told apart by its count.
+/// `claimed` holds how many deletions of each entry were attributed already.
+pub fn correlate_deletion<'a>(
+    entries: &'a [AuditEntry],
+    claimed: &mut HashMap<u64, u64>,
+    channel_id: ChannelId,
+    author: UserId,
+    now: NaiveDateTime,
+) -> Option<&'a AuditEntry> {
+    // Entries which are new to us were only caused by recent events if they are recent themselves.
+    for entry in entries {
+        claimed.entry(entry.id).or_insert_with(|| {
+            if now - entry.created_at <= ATTRIBUTION_WINDOW {
+                0
+            } else {
+                entry.count
+            }
+        });
+    }
+    claimed.retain(|id, _| entries.iter().any(|entry| entry.id == *id));
+
+    let entry = entries.iter().find(|entry| {
+        entry.target_id == Some(author.get())
+            && entry.channel_id == Some(channel_id)
+            && entry.count > claimed[&entry.id]
+    })?;
+    *claimed.entry(entry.id).or_default() += 1;
+    Some(entry)
+}
+
+/// The latest entry about `target` which is recent enough to have caused an event seen at `now`.
+pub fn correlate_target(
+    entries: &[AuditEntry],
+    target: u64,
+    now: NaiveDateTime,
+) -> Option<&AuditEntry> {
+    entries
+        .iter()
+        .find(|entry| entry.target_id == Some(target))
+        .filter(|entry| now - entry.created_at <= ATTRIBUTION_WINDOW)
+}
+
+/// Looks up who caused an event about `target`, given the action it's logged as.
+pub async fn find_actor<S: AuditSource>(
+    source: &S,
+    guild_id: GuildId,
+    action: Action,
+    target: u64,
+    now: NaiveDateTime,
+) -> DoomResult<Option<AuditEntry>> {
+    let entries = source.entries(guild_id, action, LOOKUP_LIMIT).await?;
+    Ok(correlate_target(&entries, target, now).cloned())
+}
+
+/// Whether the author removed their own message, given who the audit log says deleted it.
+/// Only then can a deletion be a ghost ping.
+pub fn deleted_by_author(author: UserId, deleter: Option<&AuditEntry>) -> bool {
+    deleter.is_none_or(|entry| entry.user_id == author)
+}
+
+/// Keeps track of which deletions in the audit log were attributed already.
+#[derive(Default)]
+pub struct DeletionTracker {
+    claimed: Mutex<HashMap<GuildId, HashMap<u64, u64>>>,
+}
+
+impl DeletionTracker {
+    /// Looks up who deleted a message of `author`, if it wasn't the author themselves.
+    pub async fn find_deleter<S: AuditSource>(
+        &self,
+        source: &S,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        author: UserId,
+        now: NaiveDateTime,
+    ) -> DoomResult<Option<AuditEntry>> {
+        let entries = source
+            .entries(
+                guild_id,
+                Action::Message(MessageAction::Delete),
+                LOOKUP_LIMIT,
+            )
+            .await?;
+        let mut claimed = self.claimed.lock().unwrap_or_else(|e| e.into_inner());
+        let claimed = claimed.entry(guild_id).or_default();
+        Ok(correlate_deletion(&entries, claimed, channel_id, author, now).cloned())
+    }
+}
@@ -0,0 +1,289 @@
+use crate::config::LogCategory;
+use crate::persistence::models::LogRoute;
+use crate::persistence::{delete_log_route, save_guild_config, save_log_route};
+use crate::{Context, Error};
+use log::info;
+use poise::serenity_prelude::{Colour, CreateEmbed, CreateMessage, GuildChannel, Mentionable};
+use poise::{ChoiceParameter, CreateReply};
+
+/// Configures the logging of this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("show", "log_channel", "route", "ghost_ping_dm", "new_account_days"),
+    subcommand_required,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Checks that logs can be sent to the channel, replying with the reason if they can't.
+async fn check_log_channel(ctx: Context<'_>, channel: &GuildChannel) -> Result<bool, Error> {
+    let reason = if Some(channel.guild_id) != ctx.guild_id() {
+        ":octagonal_sign: The channel has to be part of this server.".to_string()
+    } else {
+        // Fails early if the bot isn't allowed to write there, rather than on the next event.
+        let greeting = CreateMessage::new().content("Logging Channel Found :white_check_mark:");
+        match channel.send_message(ctx, greeting).await {
+            Ok(_) => return Ok(true),
+            Err(_) => format!(
+                ":octagonal_sign: Can't send messages to {}.",
+                channel.mention()
+            ),
+        }
+    };
+    ctx.send(CreateReply::default().content(reason).ephemeral(true))
+        .await?;
+    Ok(false)
+}
+
+/// Shows which channels the logs of this server are sent to
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let config = &ctx.data().config;
+
+    let routes = LogCategory::ALL
+        .iter()
+        .map(|category| {
+            format!(
+                "{}: {}",
+                category.name(),
+                config
+                    .log_channel(Some(guild_id), *category)
+                    .map_or("*not logged*".to_string(), |c| c.mention().to_string())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = CreateEmbed::default()
+        .title("Logging Configuration")
+        .colour(Colour::BLURPLE)
+        .field(
+            "Log Channel",
+            config
+                .default_channel(Some(guild_id))
+                .map_or("*none*".to_string(), |c| c.mention().to_string()),
+            false,
+        )
+        .field("Categories", routes, false)
+        .field(
+            "New Accounts",
+            format!(
+                "Younger than {} days",
+                config.get(guild_id).new_account_days
+            ),
+            false,
+        )
+        .field(
+            "Ghost Ping DMs",
+            if config.get(guild_id).notify_ghost_pings {
+                "Enabled"
+            } else {
+                "Disabled"
+            },
+            false,
+        );
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets the channel the logs of this server are sent to
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "log-channel",
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn log_channel(
+    ctx: Context<'_>,
+    #[description = "Channel to send the logs to, leave empty to use the default"]
+    #[channel_types("Text", "News")]
+    channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    if let Some(channel) = &channel {
+        if !check_log_channel(ctx, channel).await? {
+            return Ok(());
+        }
+    }
+
+    let mut config = ctx.data().config.get(guild_id);
+    config.log_channel = channel.as_ref().map(|c| c.id.get() as i64);
+    let config = ctx
+        .data()
+        .db
+        .run(move |pool| save_guild_config(pool, config))
+        .await?;
+    ctx.data().config.update(config);
+    info!(
+        "Log channel of {} set to {:?} by {}",
+        guild_id,
+        channel.as_ref().map(|c| c.id),
+        ctx.author().id
+    );
+
+    let content = match ctx.data().config.default_channel(Some(guild_id)) {
+        Some(target) => format!(
+            ":white_check_mark: Logs are now sent to {}.",
+            target.mention()
+        ),
+        None => {
+            ":white_check_mark: Logging is disabled, as there is no default channel.".to_string()
+        }
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sends one category of logs to a channel of its own
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn route(
+    ctx: Context<'_>,
+    #[description = "Category of logs to route"] category: LogCategory,
+    #[description = "Channel to send the category to, leave empty to use the log channel"]
+    #[channel_types("Text", "News")]
+    channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    match &channel {
+        Some(channel) => {
+            if !check_log_channel(ctx, channel).await? {
+                return Ok(());
+            }
+            let route = LogRoute {
+                guild_id: guild_id.get() as i64,
+                category: category.key().to_string(),
+                channel_id: channel.id.get() as i64,
+            };
+            ctx.data()
+                .db
+                .run(move |pool| save_log_route(pool, route))
+                .await?;
+        }
+        None => {
+            ctx.data()
+                .db
+                .run(move |pool| delete_log_route(pool, guild_id.get(), category.key()))
+                .await?;
+        }
+    }
+    ctx.data()
+        .config
+        .set_route(guild_id, category, channel.as_ref().map(|c| c.id));
+    info!(
+        "{} logs of {} routed to {:?} by {}",
+        category.name(),
+        guild_id,
+        channel.as_ref().map(|c| c.id),
+        ctx.author().id
+    );
+
+    let content = match ctx.data().config.log_channel(Some(guild_id), category) {
+        Some(target) => format!(
+            ":white_check_mark: {} are now sent to {}.",
+            category.name(),
+            target.mention()
+        ),
+        None => format!(
+            ":white_check_mark: {} aren't logged, as there is no default channel.",
+            category.name()
+        ),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets whether ghost pinged users are notified via DM
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "ghost-ping-dm",
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn ghost_ping_dm(
+    ctx: Context<'_>,
+    #[description = "Whether to send the pinged users a DM"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let mut config = ctx.data().config.get(guild_id);
+    config.notify_ghost_pings = enabled;
+    let config = ctx
+        .data()
+        .db
+        .run(move |pool| save_guild_config(pool, config))
+        .await?;
+    ctx.data().config.update(config);
+    info!(
+        "Ghost ping DMs of {} set to {} by {}",
+        guild_id,
+        enabled,
+        ctx.author().id
+    );
+
+    let content = if enabled {
+        ":white_check_mark: Ghost pinged users are now notified via DM."
+    } else {
+        ":white_check_mark: Ghost pinged users are no longer notified."
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sets how young accounts are flagged as new when they join
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "new-account-days",
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn new_account_days(
+    ctx: Context<'_>,
+    #[description = "Accounts younger than this many days are flagged"]
+    #[min = 0]
+    #[max = 365]
+    days: u16,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let mut config = ctx.data().config.get(guild_id);
+    config.new_account_days = i32::from(days);
+    let config = ctx
+        .data()
+        .db
+        .run(move |pool| save_guild_config(pool, config))
+        .await?;
+    ctx.data().config.update(config);
+    info!(
+        "New account threshold of {} set to {} days by {}",
+        guild_id,
+        days,
+        ctx.author().id
+    );
+
+    let content = format!(
+        ":white_check_mark: Accounts younger than {} days are now flagged when they join.",
+        days
+    );
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
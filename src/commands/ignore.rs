@@ -0,0 +1,167 @@
+use crate::config::ignore::IgnoreKind;
+use crate::persistence::models::IgnoreRule;
+use crate::persistence::{create_ignore_rule, delete_ignore_rule};
+use crate::util::discord::{join_limited, FIELD_LIMIT};
+use crate::{Context, Error};
+use log::info;
+use poise::serenity_prelude::{ChannelType, Colour, CreateEmbed, GuildChannel, Role, User};
+use poise::CreateReply;
+
+/// Manages the channels, roles and users which are neither stored nor logged
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("add", "remove", "list"),
+    subcommand_required,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn ignore(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Adds or removes the rule for exactly one of the targets.
+async fn update_rule(
+    ctx: Context<'_>,
+    channel: Option<GuildChannel>,
+    role: Option<Role>,
+    user: Option<User>,
+    ignored: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let targets: Vec<(IgnoreKind, u64)> = [
+        channel.map(|c| match c.kind {
+            ChannelType::Category => (IgnoreKind::Category, c.id.get()),
+            _ => (IgnoreKind::Channel, c.id.get()),
+        }),
+        role.map(|r| (IgnoreKind::Role, r.id.get())),
+        user.map(|u| (IgnoreKind::User, u.id.get())),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let [(kind, id)] = targets[..] else {
+        let reply = CreateReply::default()
+            .content(":octagonal_sign: Pick exactly one channel, category, role or user.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let rule = IgnoreRule {
+        guild_id: guild_id.get() as i64,
+        kind: kind.key().to_string(),
+        target_id: id as i64,
+    };
+    let changed = ctx
+        .data()
+        .db
+        .run(move |pool| {
+            if ignored {
+                create_ignore_rule(pool, rule)
+            } else {
+                delete_ignore_rule(pool, rule)
+            }
+        })
+        .await?;
+    ctx.data().config.set_ignored(guild_id, kind, id, ignored);
+    info!(
+        "{} {} {} in {} by {}",
+        if ignored {
+            "Ignoring"
+        } else {
+            "No longer ignoring"
+        },
+        kind.key(),
+        id,
+        guild_id,
+        ctx.author().id
+    );
+
+    let content = match (ignored, changed) {
+        (true, true) => format!(":white_check_mark: {} is now ignored.", kind.mention(id)),
+        (true, false) => format!(":mag_right: {} is already ignored.", kind.mention(id)),
+        (false, true) => format!(
+            ":white_check_mark: {} is no longer ignored.",
+            kind.mention(id)
+        ),
+        (false, false) => format!(":mag_right: {} isn't ignored.", kind.mention(id)),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Stops storing and logging messages of a channel, category, role or user
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Channel, thread or category to ignore"] channel: Option<GuildChannel>,
+    #[description = "Role to ignore"] role: Option<Role>,
+    #[description = "User to ignore"] user: Option<User>,
+) -> Result<(), Error> {
+    update_rule(ctx, channel, role, user, true).await
+}
+
+/// Resumes storing and logging messages of a channel, category, role or user
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Channel, thread or category to stop ignoring"] channel: Option<GuildChannel>,
+    #[description = "Role to stop ignoring"] role: Option<Role>,
+    #[description = "User to stop ignoring"] user: Option<User>,
+) -> Result<(), Error> {
+    update_rule(ctx, channel, role, user, false).await
+}
+
+/// Lists what is ignored in this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let rules = ctx.data().config.ignore_rules(guild_id);
+    if rules.is_empty() {
+        let reply = CreateReply::default()
+            .content(":mag_right: Nothing is ignored in this server.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Ignored")
+        .colour(Colour::LIGHT_GREY);
+    for (kind, name) in [
+        (IgnoreKind::Channel, "Channels"),
+        (IgnoreKind::Category, "Categories"),
+        (IgnoreKind::Role, "Roles"),
+        (IgnoreKind::User, "Users"),
+    ] {
+        let targets: Vec<String> = rules
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, id)| kind.mention(id))
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+        embed = embed.field(name, join_limited(&targets, "\n", FIELD_LIMIT), false);
+    }
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::persistence::get_top_inviters;
+use crate::{Context, Error};
+use poise::serenity_prelude::{Colour, CreateEmbed, Mentionable, UserId};
+use poise::CreateReply;
+
+/// How many inviters the leaderboard lists.
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// Shows how members found their way into this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("leaderboard"),
+    subcommand_required,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn invites(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Lists the members whose invites brought in the most joins
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    default_member_permissions = "MANAGE_GUILD"
+)]
+pub async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let inviters = ctx
+        .data()
+        .db
+        .run(move |pool| get_top_inviters(pool, guild_id.get(), LEADERBOARD_SIZE))
+        .await?;
+    if inviters.is_empty() {
+        let reply = CreateReply::default()
+            .content(":mag_right: No joins through invites are recorded yet.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = inviters
+        .iter()
+        .enumerate()
+        .map(|(rank, (inviter, joins))| {
+            format!(
+                "**{}.** {} with {} join(s)",
+                rank + 1,
+                UserId::new(*inviter as u64).mention(),
+                joins
+            )
+        })
+        .collect();
+    let embed = CreateEmbed::default()
+        .title("Invite Leaderboard")
+        .colour(Colour::BLURPLE)
+        .description(lines.join("\n"));
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -1,3 +1,17 @@
+mod config;
+mod ignore;
+mod invites;
+mod modlog;
+mod revisions;
+mod voice_history;
+
+pub use config::config;
+pub use ignore::ignore;
+pub use invites::invites;
+pub use modlog::modlog;
+pub use revisions::revisions;
+pub use voice_history::voice_history;
+
 use crate::util::discord::{build_changelog, limit_content_and_see_more};
 use crate::{Context, Error};
 use log::debug;
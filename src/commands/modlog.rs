@@ -0,0 +1,80 @@
+use crate::event::moderation::ModerationKind;
+use crate::persistence::get_moderation_actions;
+use crate::util::discord::{format_timestamp, join_limited, truncate, FIELD_LIMIT};
+use crate::{Context, Error};
+use poise::serenity_prelude::{Colour, CreateEmbed, Mentionable, User, UserId};
+use poise::CreateReply;
+
+/// How many of the latest actions are listed.
+const LISTED_ACTIONS: usize = 10;
+
+/// Shows the bans, unbans, kicks and AutoMod actions of a user in this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MODERATE_MEMBERS",
+    default_member_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn modlog(
+    ctx: Context<'_>,
+    #[description = "User to show the moderation history of"] user: User,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let user_id = user.id;
+    let actions = ctx
+        .data()
+        .db
+        .run(move |pool| get_moderation_actions(pool, guild_id.get(), user_id.get()))
+        .await?;
+    if actions.is_empty() {
+        let reply = CreateReply::default()
+            .content(format!(
+                ":mag_right: No moderation actions are recorded for {}.",
+                user.mention()
+            ))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let counts: Vec<String> = ModerationKind::ALL
+        .iter()
+        .filter_map(|kind| {
+            let count = actions.iter().filter(|a| a.kind == kind.key()).count();
+            (count > 0).then(|| format!("{}: {}", kind.name(), count))
+        })
+        .collect();
+    let history: Vec<String> = actions
+        .iter()
+        .take(LISTED_ACTIONS)
+        .map(|action| {
+            let kind = ModerationKind::from_key(&action.kind);
+            let moderator = match action.moderator_id {
+                Some(id) => UserId::new(id as u64).mention().to_string(),
+                None if kind == Some(ModerationKind::AutoMod) => "AutoMod".to_string(),
+                None => "*unknown*".to_string(),
+            };
+            let mut line = format!(
+                "{} **{}** by {}",
+                format_timestamp(action.created_at),
+                kind.map_or("Unknown", |k| k.name()),
+                moderator
+            );
+            if let Some(reason) = &action.reason {
+                line.push_str(&format!(": {}", truncate(reason, 100)));
+            }
+            line
+        })
+        .collect();
+
+    let embed = CreateEmbed::default()
+        .title("Moderation History")
+        .colour(Colour::DARK_RED)
+        .thumbnail(user.face())
+        .field("User", format!("{} ({})", user.mention(), user.id), false)
+        .field("Total", counts.join("\n"), false)
+        .field("Latest", join_limited(&history, "\n", FIELD_LIMIT), false);
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use crate::persistence::get_message_revisions;
+use crate::util::discord::{format_timestamp, truncate, FIELD_LIMIT};
+use crate::{Context, Error};
+use poise::serenity_prelude::{Colour, CreateEmbed, MessageId};
+use poise::CreateReply;
+
+const PREVIEW_LENGTH: usize = 200;
+
+fn preview(content: &str, limit: usize) -> String {
+    if content.is_empty() {
+        return "*<empty>*".to_string();
+    }
+    truncate(content, limit)
+}
+
+/// Shows the stored edit history of a message
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    default_member_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn revisions(
+    ctx: Context<'_>,
+    #[description = "ID of the message to show the history for"] message_id: String,
+    #[description = "Only show this revision (1 is the original message)"] revision: Option<i64>,
+) -> Result<(), Error> {
+    let message_id = match message_id.trim().parse::<u64>() {
+        Ok(id) if id != 0 => MessageId::new(id),
+        _ => {
+            let reply = CreateReply::default()
+                .content(":octagonal_sign: Not a proper Discord Snowflake.")
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let revisions = ctx
+        .data()
+        .db
+        .run(move |pool| get_message_revisions(pool, message_id.get()))
+        .await?;
+    let total = revisions.len();
+    if total == 0 {
+        let reply = CreateReply::default()
+            .content(":mag_right: No revisions are stored for this message.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("Message Revisions")
+        .colour(Colour::ORANGE)
+        .description(format!("Message `{}`", message_id));
+
+    match revision {
+        Some(number) => match revisions.iter().find(|r| i64::from(r.revision) == number) {
+            Some(selected) => {
+                embed = embed
+                    .field(
+                        "Revision",
+                        format!("{} of {}", selected.revision, total),
+                        true,
+                    )
+                    .field("Edited", format_timestamp(selected.edited_at), true)
+                    .field("Content", preview(&selected.content, FIELD_LIMIT), false);
+            }
+            None => {
+                let reply = CreateReply::default()
+                    .content(format!(
+                        ":mag_right: Revision {} doesn't exist, the message has {} revisions.",
+                        number, total
+                    ))
+                    .ephemeral(true);
+                ctx.send(reply).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            // Discord caps embeds at 25 fields, so only the most recent revisions are listed.
+            for selected in revisions.iter().rev().take(25).rev() {
+                embed = embed.field(
+                    format!("Revision {} of {}", selected.revision, total),
+                    format!(
+                        "{}\n{}",
+                        format_timestamp(selected.edited_at),
+                        preview(&selected.content, PREVIEW_LENGTH)
+                    ),
+                    false,
+                );
+            }
+        }
+    }
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
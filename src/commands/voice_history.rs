@@ -0,0 +1,79 @@
+use crate::persistence::get_voice_sessions;
+use crate::util::discord::{format_duration, format_timestamp, join_limited, FIELD_LIMIT};
+use crate::{Context, Error};
+use chrono::TimeDelta;
+use poise::serenity_prelude::{ChannelId, Colour, CreateEmbed, Mentionable, Timestamp, User};
+use poise::CreateReply;
+
+/// How many of the latest sessions are listed.
+const LISTED_SESSIONS: usize = 10;
+
+/// Shows the time a user spent in the voice channels of this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "voice-history",
+    required_permissions = "MODERATE_MEMBERS",
+    default_member_permissions = "MODERATE_MEMBERS"
+)]
+pub async fn voice_history(
+    ctx: Context<'_>,
+    #[description = "User to show the voice sessions of"] user: User,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or("Only available within servers.")?;
+    let user_id = user.id;
+    let sessions = ctx
+        .data()
+        .db
+        .run(move |pool| get_voice_sessions(pool, guild_id.get(), user_id.get()))
+        .await?;
+    if sessions.is_empty() {
+        let reply = CreateReply::default()
+            .content(format!(
+                ":mag_right: No voice sessions are recorded for {}.",
+                user.mention()
+            ))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let now = Timestamp::now().naive_utc();
+    let total: TimeDelta = sessions
+        .iter()
+        .filter_map(|session| session.left_at.map(|left| left - session.joined_at))
+        .sum();
+    let listed: Vec<String> = sessions
+        .iter()
+        .enumerate()
+        .take(LISTED_SESSIONS)
+        .map(|(index, session)| {
+            let duration = match session.left_at {
+                Some(left) => format_duration(left - session.joined_at),
+                // Only the latest session can still be going on, older ones ended while offline.
+                None if index == 0 => {
+                    format!("{} so far", format_duration(now - session.joined_at))
+                }
+                None => "*unknown*".to_string(),
+            };
+            format!(
+                "{} {} for {}",
+                format_timestamp(session.joined_at),
+                ChannelId::new(session.channel_id as u64).mention(),
+                duration
+            )
+        })
+        .collect();
+
+    let embed = CreateEmbed::default()
+        .title("Voice History")
+        .colour(Colour::BLURPLE)
+        .thumbnail(user.face())
+        .field("User", format!("{} ({})", user.mention(), user.id), false)
+        .field("Sessions", sessions.len().to_string(), true)
+        .field("Total Time", format_duration(total), true)
+        .field("Latest", join_limited(&listed, "\n", FIELD_LIMIT), false);
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use poise::serenity_prelude::{ChannelId, Mentionable, RoleId, UserId};
+use std::collections::BTreeSet;
+
+/// What the id of an ignore rule refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IgnoreKind {
+    Channel,
+    Category,
+    Role,
+    User,
+}
+
+impl IgnoreKind {
+    pub const ALL: [IgnoreKind; 4] = [
+        IgnoreKind::Channel,
+        IgnoreKind::Category,
+        IgnoreKind::Role,
+        IgnoreKind::User,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            IgnoreKind::Channel => "channel",
+            IgnoreKind::Category => "category",
+            IgnoreKind::Role => "role",
+            IgnoreKind::User => "user",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.key() == key)
+    }
+
+    /// Renders the target of a rule of this kind as a mention.
+    pub fn mention(self, id: u64) -> String {
+        match self {
+            IgnoreKind::Channel | IgnoreKind::Category => ChannelId::new(id).mention().to_string(),
+            IgnoreKind::Role => RoleId::new(id).mention().to_string(),
+            IgnoreKind::User => UserId::new(id).mention().to_string(),
+        }
+    }
+}
+
+/// Where an event happened and who caused it, as far as the ignore rules are concerned.
+pub struct EventScope<'a> {
+    /// The channel followed by its parents, i.e. thread, channel and category.
+    pub channels: &'a [ChannelId],
+    pub user: Option<UserId>,
+    pub roles: &'a [RoleId],
+}
+
+/// The ignore rules of a guild.
+#[derive(Clone, Default)]
+pub struct IgnoreRules {
+    rules: BTreeSet<(IgnoreKind, u64)>,
+}
+
+impl IgnoreRules {
+    pub fn insert(&mut self, kind: IgnoreKind, id: u64) {
+        self.rules.insert((kind, id));
+    }
+
+    pub fn remove(&mut self, kind: IgnoreKind, id: u64) {
+        self.rules.remove(&(kind, id));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IgnoreKind, u64)> + '_ {
+        self.rules.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn matches(&self, scope: &EventScope) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        // A channel rule covers the threads of the channel as well.
+        let channel = scope.channels.iter().any(|id| {
+            self.rules.contains(&(IgnoreKind::Channel, id.get()))
+                || self.rules.contains(&(IgnoreKind::Category, id.get()))
+        });
+        let user = scope
+            .user
+            .is_some_and(|id| self.rules.contains(&(IgnoreKind::User, id.get())));
+        let role = scope
+            .roles
+            .iter()
+            .any(|id| self.rules.contains(&(IgnoreKind::Role, id.get())));
+        channel || user || role
+    }
+}
@@ -0,0 +1,240 @@
+pub(crate) mod ignore;
+
+use crate::error::DoomResult;
+use crate::persistence::models::{GuildConfig, IgnoreRule, LogRoute};
+use crate::persistence::{get_guild_configs, get_ignore_rules, get_log_routes, SqlitePool};
+use ignore::{EventScope, IgnoreKind, IgnoreRules};
+use log::warn;
+use poise::serenity_prelude::{ChannelId, GuildId};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Kinds of log messages, each of which can be routed to a channel of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, poise::ChoiceParameter)]
+pub enum LogCategory {
+    #[name = "Message Edits"]
+    MessageEdits,
+    #[name = "Message Deletes"]
+    MessageDeletes,
+    #[name = "Member Events"]
+    MemberEvents,
+    #[name = "Moderation"]
+    Moderation,
+    #[name = "Ghost Pings"]
+    GhostPings,
+    #[name = "Server Changes"]
+    ServerChanges,
+    #[name = "Voice Activity"]
+    Voice,
+    #[name = "Threads"]
+    Threads,
+    #[name = "Bot Status"]
+    BotStatus,
+}
+
+impl LogCategory {
+    pub const ALL: [LogCategory; 9] = [
+        LogCategory::MessageEdits,
+        LogCategory::MessageDeletes,
+        LogCategory::MemberEvents,
+        LogCategory::Moderation,
+        LogCategory::GhostPings,
+        LogCategory::ServerChanges,
+        LogCategory::Voice,
+        LogCategory::Threads,
+        LogCategory::BotStatus,
+    ];
+
+    /// Name the category is stored as, which mustn't change with its display name.
+    pub fn key(self) -> &'static str {
+        match self {
+            LogCategory::MessageEdits => "message_edits",
+            LogCategory::MessageDeletes => "message_deletes",
+            LogCategory::MemberEvents => "member_events",
+            LogCategory::Moderation => "moderation",
+            LogCategory::GhostPings => "ghost_pings",
+            LogCategory::ServerChanges => "server_changes",
+            LogCategory::Voice => "voice",
+            LogCategory::Threads => "threads",
+            LogCategory::BotStatus => "bot_status",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|category| category.key() == key)
+    }
+}
+
+#[derive(Default)]
+struct GuildEntry {
+    config: Option<GuildConfig>,
+    routes: HashMap<LogCategory, ChannelId>,
+    ignored: IgnoreRules,
+}
+
+/// In-memory copy of the per-guild configuration, so events can be routed without a query.
+pub struct ConfigCache {
+    default_log_channel: Option<ChannelId>,
+    guilds: RwLock<HashMap<GuildId, GuildEntry>>,
+}
+
+impl ConfigCache {
+    pub fn new(
+        default_log_channel: Option<ChannelId>,
+        configs: Vec<GuildConfig>,
+        routes: Vec<LogRoute>,
+        rules: Vec<IgnoreRule>,
+    ) -> Self {
+        let mut guilds: HashMap<GuildId, GuildEntry> = HashMap::new();
+        for config in configs.into_iter().filter(|config| config.guild_id != 0) {
+            let guild_id = GuildId::new(config.guild_id as u64);
+            guilds.entry(guild_id).or_default().config = Some(config);
+        }
+        for route in routes
+            .into_iter()
+            .filter(|route| route.guild_id != 0 && route.channel_id != 0)
+        {
+            match LogCategory::from_key(&route.category) {
+                Some(category) => {
+                    guilds
+                        .entry(GuildId::new(route.guild_id as u64))
+                        .or_default()
+                        .routes
+                        .insert(category, ChannelId::new(route.channel_id as u64));
+                }
+                None => warn!(
+                    "Ignoring route of unknown category '{}' in guild {}",
+                    route.category, route.guild_id
+                ),
+            }
+        }
+        for rule in rules
+            .into_iter()
+            .filter(|rule| rule.guild_id != 0 && rule.target_id != 0)
+        {
+            match IgnoreKind::from_key(&rule.kind) {
+                Some(kind) => guilds
+                    .entry(GuildId::new(rule.guild_id as u64))
+                    .or_default()
+                    .ignored
+                    .insert(kind, rule.target_id as u64),
+                None => warn!(
+                    "Ignoring ignore rule of unknown kind '{}' in guild {}",
+                    rule.kind, rule.guild_id
+                ),
+            }
+        }
+        ConfigCache {
+            default_log_channel,
+            guilds: RwLock::new(guilds),
+        }
+    }
+
+    pub fn load(pool: &SqlitePool, default_log_channel: Option<ChannelId>) -> DoomResult<Self> {
+        Ok(Self::new(
+            default_log_channel,
+            get_guild_configs(pool)?,
+            get_log_routes(pool)?,
+            get_ignore_rules(pool)?,
+        ))
+    }
+
+    // A panic while holding the lock can't leave a half-written config behind.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<GuildId, GuildEntry>> {
+        self.guilds.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<GuildId, GuildEntry>> {
+        self.guilds.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the stored configuration of a guild, or a blank one if it has none yet.
+    pub fn get(&self, guild_id: GuildId) -> GuildConfig {
+        self.read()
+            .get(&guild_id)
+            .and_then(|entry| entry.config.clone())
+            .unwrap_or_else(|| GuildConfig {
+                guild_id: guild_id.get() as i64,
+                ..Default::default()
+            })
+    }
+
+    pub fn update(&self, config: GuildConfig) {
+        let guild_id = GuildId::new(config.guild_id as u64);
+        self.write().entry(guild_id).or_default().config = Some(config);
+    }
+
+    /// Routes a category to a channel, `None` sends it to the log channel again.
+    pub fn set_route(&self, guild_id: GuildId, category: LogCategory, channel: Option<ChannelId>) {
+        let mut guilds = self.write();
+        let routes = &mut guilds.entry(guild_id).or_default().routes;
+        match channel {
+            Some(channel) => routes.insert(category, channel),
+            None => routes.remove(&category),
+        };
+    }
+
+    pub fn ignore_rules(&self, guild_id: GuildId) -> IgnoreRules {
+        self.read()
+            .get(&guild_id)
+            .map(|entry| entry.ignored.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_ignored(&self, guild_id: GuildId, kind: IgnoreKind, id: u64, ignored: bool) {
+        let mut guilds = self.write();
+        let rules = &mut guilds.entry(guild_id).or_default().ignored;
+        if ignored {
+            rules.insert(kind, id);
+        } else {
+            rules.remove(kind, id);
+        }
+    }
+
+    /// Whether events of the scope are neither stored nor logged in the guild.
+    pub fn is_ignored(&self, guild_id: GuildId, scope: &EventScope) -> bool {
+        self.read()
+            .get(&guild_id)
+            .is_some_and(|entry| entry.ignored.matches(scope))
+    }
+
+    /// The channel logs of a guild are sent to, falling back to `LOG_CHANNEL`.
+    pub fn default_channel(&self, guild_id: Option<GuildId>) -> Option<ChannelId> {
+        guild_id
+            .and_then(|id| {
+                self.read()
+                    .get(&id)
+                    .and_then(|entry| entry.config.as_ref())
+                    .and_then(|config| config.log_channel)
+            })
+            .map(|channel| ChannelId::new(channel as u64))
+            .or(self.default_log_channel)
+    }
+
+    /// The channel a category of logs is sent to, falling back to the default channel.
+    pub fn log_channel(
+        &self,
+        guild_id: Option<GuildId>,
+        category: LogCategory,
+    ) -> Option<ChannelId> {
+        guild_id
+            .and_then(|id| {
+                self.read()
+                    .get(&id)
+                    .and_then(|entry| entry.routes.get(&category).copied())
+            })
+            .or_else(|| self.default_channel(guild_id))
+    }
+
+    /// Every channel a category is sent to, i.e. for the greetings on startup and shutdown.
+    pub fn log_channels(&self, category: LogCategory) -> Vec<ChannelId> {
+        let guilds: Vec<GuildId> = self.read().keys().copied().collect();
+        guilds
+            .into_iter()
+            .filter_map(|guild_id| self.log_channel(Some(guild_id), category))
+            .chain(self.default_log_channel)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
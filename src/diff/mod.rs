@@ -0,0 +1,267 @@
+use crate::util::discord::escape_markdown;
+
+/// Tables larger than this skip the common subsequence, so huge edits stay cheap.
+const MAX_CELLS: usize = 4_000_000;
+/// Unchanged text kept around each change when rendering.
+const CONTEXT: [usize; 4] = [48, 24, 8, 0];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    Equal(String),
+    Removed(String),
+    Added(String),
+}
+
+impl Change {
+    fn text(&self) -> &str {
+        match self {
+            Change::Equal(text) | Change::Removed(text) | Change::Added(text) => text,
+        }
+    }
+}
+
+/// Splits text into words and the whitespace between them, so the tokens join back up to it.
+fn words(text: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut whitespace = None;
+    for (index, c) in text.char_indices() {
+        if whitespace.is_some_and(|w| w != c.is_whitespace()) {
+            tokens.push(&text[start..index]);
+            start = index;
+        }
+        whitespace = Some(c.is_whitespace());
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+fn chars(text: &str) -> Vec<&str> {
+    text.char_indices()
+        .map(|(index, c)| &text[index..index + c.len_utf8()])
+        .collect()
+}
+
+/// Appends a change, merging it into the previous one of the same kind.
+fn push(changes: &mut Vec<Change>, change: Change) {
+    match (changes.last_mut(), &change) {
+        (Some(Change::Equal(last)), Change::Equal(text))
+        | (Some(Change::Removed(last)), Change::Removed(text))
+        | (Some(Change::Added(last)), Change::Added(text)) => last.push_str(text),
+        _ => {
+            if !change.text().is_empty() {
+                changes.push(change)
+            }
+        }
+    }
+}
+
+/// Diffs two token sequences along their longest common subsequence.
+fn diff_tokens(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut changes = vec![];
+    push(&mut changes, Change::Equal(old[..prefix].concat()));
+    if a.len().saturating_mul(b.len()) > MAX_CELLS {
+        push(&mut changes, Change::Removed(a.concat()));
+        push(&mut changes, Change::Added(b.concat()));
+    } else {
+        // lengths[i][j] is the length of the common subsequence of a[i..] and b[j..].
+        let width = b.len() + 1;
+        let mut lengths = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i * width + j] = if a[i] == b[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                push(&mut changes, Change::Equal(a[i].to_string()));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                push(&mut changes, Change::Removed(a[i].to_string()));
+                i += 1;
+            } else {
+                push(&mut changes, Change::Added(b[j].to_string()));
+                j += 1;
+            }
+        }
+        push(&mut changes, Change::Removed(a[i..].concat()));
+        push(&mut changes, Change::Added(b[j..].concat()));
+    }
+    push(
+        &mut changes,
+        Change::Equal(old[old.len() - suffix..].concat()),
+    );
+    changes
+}
+
+/// Diffs two texts word by word. A word replaced by a similar one, i.e. a typo fix, is diffed
+/// character by character instead, and so is changed whitespace.
+pub fn diff(old: &str, new: &str) -> Vec<Change> {
+    let mut changes = vec![];
+    let mut words = diff_tokens(&self::words(old), &self::words(new)).into_iter();
+    while let Some(change) = words.next() {
+        let (Change::Removed(removed), Some(Change::Added(added))) =
+            (&change, words.as_slice().first())
+        else {
+            push(&mut changes, change);
+            continue;
+        };
+        let whitespace = |text: &str| text.chars().all(char::is_whitespace);
+        let single_word = |text: &str| !text.contains(char::is_whitespace);
+        if (single_word(removed) && single_word(added))
+            || (whitespace(removed) && whitespace(added))
+        {
+            let refined = diff_tokens(&chars(removed), &chars(added));
+            let common: usize = refined
+                .iter()
+                .filter_map(|c| match c {
+                    Change::Equal(text) => Some(text.chars().count()),
+                    _ => None,
+                })
+                .sum();
+            if common * 2 >= removed.chars().count().max(added.chars().count()) {
+                refined.into_iter().for_each(|c| push(&mut changes, c));
+                words.next();
+                continue;
+            }
+        }
+        push(&mut changes, change);
+    }
+    changes
+}
+
+/// Wraps changed text in markers, keeping its surrounding whitespace outside of them, as
+/// Discord doesn't render markers next to whitespace. Pure whitespace is made visible.
+fn mark(text: &str, marker: &str) -> String {
+    let core = text.trim();
+    if core.is_empty() {
+        let visible: String = text
+            .chars()
+            .map(|c| if c == '\n' { '↵' } else { '·' })
+            .collect();
+        return format!("{}{}{}", marker, visible, marker);
+    }
+    let start = text.len() - text.trim_start().len();
+    let end = start + core.len();
+    format!(
+        "{}{}{}{}{}",
+        &text[..start],
+        marker,
+        escape_markdown(core),
+        marker,
+        &text[end..]
+    )
+}
+
+/// Renders each change on its own, so a rendering can be cut between changes.
+fn render_with(changes: &[Change], context: usize) -> Vec<String> {
+    let mut pieces = Vec::with_capacity(changes.len());
+    for (index, change) in changes.iter().enumerate() {
+        let piece = match change {
+            Change::Equal(text) => {
+                let chars: Vec<char> = text.chars().collect();
+                let (first, last) = (index == 0, index == changes.len() - 1);
+                let kept = if first || last { context } else { context * 2 };
+                if chars.len() <= kept + 1 {
+                    pieces.push(escape_markdown(text));
+                    continue;
+                }
+                let head: String = chars[..context].iter().collect();
+                let tail: String = chars[chars.len() - context..].iter().collect();
+                let mut piece = String::new();
+                match (first, last) {
+                    (true, _) => piece.push('…'),
+                    (_, true) => {
+                        piece.push_str(&escape_markdown(&head));
+                        piece.push('…');
+                    }
+                    _ => {
+                        piece.push_str(&escape_markdown(&head));
+                        piece.push_str(" … ");
+                    }
+                }
+                if !last {
+                    piece.push_str(&escape_markdown(&tail));
+                }
+                piece
+            }
+            Change::Removed(text) => mark(text, "~~"),
+            Change::Added(text) => mark(text, "**"),
+        };
+        pieces.push(piece);
+    }
+    pieces
+}
+
+/// The longest start of a change which fits into `room` characters, with its markers intact.
+fn shorten(change: &Change, piece: &str, room: usize) -> String {
+    let (text, marker) = match change {
+        Change::Removed(text) => (text, "~~"),
+        Change::Added(text) => (text, "**"),
+        // Unchanged text carries no markers, so only a dangling escape has to go.
+        Change::Equal(_) => {
+            let shortened: String = piece.chars().take(room).collect();
+            return shortened.trim_end_matches('\\').to_string();
+        }
+    };
+    let chars: Vec<char> = text.chars().collect();
+    (1..=chars.len().min(room))
+        .rev()
+        .map(|take| mark(&chars[..take].iter().collect::<String>(), marker))
+        .find(|marked| marked.chars().count() <= room)
+        .unwrap_or_default()
+}
+
+/// Renders a diff with ~~removed~~ and **added** text, shortening the unchanged text in between
+/// until it fits into `limit` characters.
+pub fn render(changes: &[Change], limit: usize) -> String {
+    let mut pieces = vec![];
+    for context in CONTEXT {
+        pieces = render_with(changes, context);
+        if pieces
+            .iter()
+            .map(|piece| piece.chars().count())
+            .sum::<usize>()
+            <= limit
+        {
+            return pieces.concat();
+        }
+    }
+
+    // Cuts between changes, so no marker is left open.
+    let budget = limit.saturating_sub(1);
+    let mut rendered = String::new();
+    let mut length = 0;
+    for (change, piece) in changes.iter().zip(&pieces) {
+        let count = piece.chars().count();
+        if length + count > budget {
+            rendered.push_str(&shorten(change, piece, budget - length));
+            break;
+        }
+        rendered.push_str(piece);
+        length += count;
+    }
+    rendered.push('…');
+    rendered
+}
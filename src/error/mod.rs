@@ -1,11 +1,28 @@
+use poise::serenity_prelude as serenity;
 use std::fmt;
 
+pub type DoomResult<T> = Result<T, DoomError>;
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum DoomError {
     NotImplementedError { functionality: String },
+    DatabaseError(diesel::result::Error),
+    PoolError(r2d2::Error),
+    DiscordError(Box<serenity::Error>),
+    ConfigError { variable: String, reason: String },
 }
 
-impl std::error::Error for DoomError {}
+impl std::error::Error for DoomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DoomError::DatabaseError(error) => Some(error),
+            DoomError::PoolError(error) => Some(error),
+            DoomError::DiscordError(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for DoomError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -15,6 +32,42 @@ impl fmt::Display for DoomError {
                 "This functionality '{}' is not implemented.",
                 functionality
             ),
+            DoomError::DatabaseError(error) => write!(f, "Database query failed: {}", error),
+            DoomError::PoolError(error) => {
+                write!(f, "No database connection available: {}", error)
+            }
+            DoomError::DiscordError(error) => write!(f, "Discord request failed: {}", error),
+            DoomError::ConfigError { variable, reason } => {
+                write!(f, "Invalid configuration of '{}': {}", variable, reason)
+            }
         }
     }
 }
+
+impl DoomError {
+    /// A database failure which didn't come from a query, i.e. migrations or the writer thread.
+    pub fn database(message: impl Into<String>) -> Self {
+        DoomError::DatabaseError(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::Unknown,
+            Box::new(message.into()),
+        ))
+    }
+}
+
+impl From<diesel::result::Error> for DoomError {
+    fn from(error: diesel::result::Error) -> Self {
+        DoomError::DatabaseError(error)
+    }
+}
+
+impl From<r2d2::Error> for DoomError {
+    fn from(error: r2d2::Error) -> Self {
+        DoomError::PoolError(error)
+    }
+}
+
+impl From<serenity::Error> for DoomError {
+    fn from(error: serenity::Error) -> Self {
+        DoomError::DiscordError(Box::new(error))
+    }
+}
@@ -0,0 +1,609 @@
+use super::{
+    construct_msg_ref, is_ignored, send_attributed_log, send_log, skip_uncached_update, Attribution,
+};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::event::moderation::ModerationKind;
+use crate::persistence::models::NewModerationAction;
+use crate::persistence::{get_message_by_id, record_moderation_action};
+use crate::util::discord::{escape_markdown, format_duration, join_limited, truncate, FIELD_LIMIT};
+use crate::Data;
+use chrono::{NaiveDateTime, TimeDelta};
+use log::warn;
+use poise::serenity_prelude::audit_log::{self, AutoModAction};
+use poise::serenity_prelude::automod::{
+    Action, ActionExecution, KeywordPresetType, Rule, Trigger, TriggerType,
+};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, Colour, CreateEmbed, CreateMessage, GuildId, Mentionable, RoleId,
+    RuleId, Timestamp, UserId,
+};
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+fn describe_trigger(kind: TriggerType) -> &'static str {
+    match kind {
+        TriggerType::Keyword => "Keyword",
+        TriggerType::Spam => "Spam",
+        TriggerType::KeywordPreset => "Keyword Preset",
+        TriggerType::MentionSpam => "Mention Spam",
+        _ => "Unknown",
+    }
+}
+
+fn describe_preset(preset: KeywordPresetType) -> &'static str {
+    match preset {
+        KeywordPresetType::Profanity => "Profanity",
+        KeywordPresetType::SexualContent => "Sexual Content",
+        KeywordPresetType::Slurs => "Slurs",
+        _ => "Unknown",
+    }
+}
+
+pub fn describe_action(action: &Action) -> String {
+    match action {
+        Action::BlockMessage { .. } => "Block Message".to_string(),
+        Action::Alert(channel) => format!("Alert in {}", channel.mention()),
+        Action::Timeout(duration) => match TimeDelta::from_std(*duration) {
+            Ok(duration) => format!("Timeout for {}", format_duration(duration)),
+            Err(_) => "Timeout".to_string(),
+        },
+        _ => "Unknown".to_string(),
+    }
+}
+
+/// The parts of an AutoMod rule which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RuleState {
+    pub name: String,
+    pub enabled: bool,
+    pub keywords: BTreeSet<String>,
+    pub regex_patterns: BTreeSet<String>,
+    pub allow_list: BTreeSet<String>,
+    pub presets: BTreeSet<String>,
+    pub mention_limit: Option<u8>,
+    pub actions: Vec<String>,
+    pub exempt_roles: BTreeSet<RoleId>,
+    pub exempt_channels: BTreeSet<ChannelId>,
+}
+
+impl RuleState {
+    pub fn from_rule(rule: &Rule) -> Self {
+        let mut state = RuleState {
+            name: rule.name.clone(),
+            enabled: rule.enabled,
+            actions: rule.actions.iter().map(describe_action).collect(),
+            exempt_roles: rule.exempt_roles.iter().copied().collect(),
+            exempt_channels: rule.exempt_channels.iter().copied().collect(),
+            ..Default::default()
+        };
+        match &rule.trigger {
+            Trigger::Keyword {
+                strings,
+                regex_patterns,
+                allow_list,
+            } => {
+                state.keywords = strings.iter().cloned().collect();
+                state.regex_patterns = regex_patterns.iter().cloned().collect();
+                state.allow_list = allow_list.iter().cloned().collect();
+            }
+            Trigger::KeywordPreset {
+                presets,
+                allow_list,
+            } => {
+                state.presets = presets
+                    .iter()
+                    .map(|preset| describe_preset(*preset).to_string())
+                    .collect();
+                state.allow_list = allow_list.iter().cloned().collect();
+            }
+            Trigger::MentionSpam {
+                mention_total_limit,
+            } => state.mention_limit = Some(*mention_total_limit),
+            _ => {}
+        }
+        state
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RuleChange {
+    Name {
+        before: String,
+        after: String,
+    },
+    Enabled(bool),
+    Keywords {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    RegexPatterns {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    AllowList {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    Presets {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    MentionLimit {
+        before: Option<u8>,
+        after: Option<u8>,
+    },
+    Actions {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    ExemptRoles {
+        added: Vec<RoleId>,
+        removed: Vec<RoleId>,
+    },
+    ExemptChannels {
+        added: Vec<ChannelId>,
+        removed: Vec<ChannelId>,
+    },
+}
+
+/// What is in `after` but not in `before`, and the other way around.
+fn set_diff<T: Ord + Clone>(before: &BTreeSet<T>, after: &BTreeSet<T>) -> (Vec<T>, Vec<T>) {
+    (
+        after.difference(before).cloned().collect(),
+        before.difference(after).cloned().collect(),
+    )
+}
+
+pub fn detect_rule_changes(before: &RuleState, after: &RuleState) -> Vec<RuleChange> {
+    let mut changes = vec![];
+    if before.name != after.name {
+        changes.push(RuleChange::Name {
+            before: before.name.clone(),
+            after: after.name.clone(),
+        });
+    }
+    if before.enabled != after.enabled {
+        changes.push(RuleChange::Enabled(after.enabled));
+    }
+    let (added, removed) = set_diff(&before.keywords, &after.keywords);
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(RuleChange::Keywords { added, removed });
+    }
+    let (added, removed) = set_diff(&before.regex_patterns, &after.regex_patterns);
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(RuleChange::RegexPatterns { added, removed });
+    }
+    let (added, removed) = set_diff(&before.allow_list, &after.allow_list);
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(RuleChange::AllowList { added, removed });
+    }
+    let (added, removed) = set_diff(&before.presets, &after.presets);
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(RuleChange::Presets { added, removed });
+    }
+    if before.mention_limit != after.mention_limit {
+        changes.push(RuleChange::MentionLimit {
+            before: before.mention_limit,
+            after: after.mention_limit,
+        });
+    }
+    if before.actions != after.actions {
+        changes.push(RuleChange::Actions {
+            before: before.actions.clone(),
+            after: after.actions.clone(),
+        });
+    }
+    let (added, removed) = set_diff(&before.exempt_roles, &after.exempt_roles);
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(RuleChange::ExemptRoles { added, removed });
+    }
+    let (added, removed) = set_diff(&before.exempt_channels, &after.exempt_channels);
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(RuleChange::ExemptChannels { added, removed });
+    }
+    changes
+}
+
+/// Lists added and removed entries as a diff.
+fn describe_entries(added: &[String], removed: &[String]) -> String {
+    let lines: Vec<String> = added
+        .iter()
+        .map(|entry| format!("+{}", entry))
+        .chain(removed.iter().map(|entry| format!("-{}", entry)))
+        .collect();
+    // Leaves room for the code block around it.
+    format!(
+        "```diff\n{}\n```",
+        truncate(&lines.join("\n"), FIELD_LIMIT - 12)
+    )
+}
+
+fn describe_mentions<T: Mentionable>(added: &[T], removed: &[T]) -> String {
+    let entries: Vec<String> = added
+        .iter()
+        .map(|id| format!("+{}", id.mention()))
+        .chain(removed.iter().map(|id| format!("-{}", id.mention())))
+        .collect();
+    join_limited(&entries, ", ", FIELD_LIMIT)
+}
+
+fn describe_list(entries: &[String]) -> String {
+    if entries.is_empty() {
+        "*none*".to_string()
+    } else {
+        entries.join(", ")
+    }
+}
+
+/// How long after an execution of a rule others for the same trigger are taken as its other actions.
+const EXECUTION_WINDOW: TimeDelta = TimeDelta::seconds(10);
+
+/// What tells the executions caused by one trigger of a rule apart from those of another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExecutionKey {
+    /// The message, or a hash of its content for blocked messages, which never get an id.
+    trigger: u64,
+    rule_id: RuleId,
+    user_id: UserId,
+}
+
+impl From<&ActionExecution> for ExecutionKey {
+    fn from(execution: &ActionExecution) -> Self {
+        let trigger = execution.message_id.map_or_else(
+            || {
+                let mut hasher = DefaultHasher::new();
+                (execution.channel_id, &execution.content).hash(&mut hasher);
+                hasher.finish()
+            },
+            |message_id| message_id.get(),
+        );
+        ExecutionKey {
+            trigger,
+            rule_id: execution.rule_id,
+            user_id: execution.user_id,
+        }
+    }
+}
+
+/// The AutoMod rules of each guild, as Discord only sends the new state of an updated rule.
+#[derive(Default)]
+pub struct AutoModCache {
+    guilds: Mutex<HashMap<GuildId, HashMap<RuleId, Rule>>>,
+    /// When each recent trigger was first executed.
+    executions: Mutex<HashMap<ExecutionKey, NaiveDateTime>>,
+}
+
+impl AutoModCache {
+    pub fn seed(&self, guild_id: GuildId, rules: Vec<Rule>) {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        guilds.insert(
+            guild_id,
+            rules.into_iter().map(|rule| (rule.id, rule)).collect(),
+        );
+    }
+
+    /// Stores the current state of a rule, returning the previous one if known.
+    pub fn insert(&self, rule: Rule) -> Option<Rule> {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        guilds
+            .entry(rule.guild_id)
+            .or_default()
+            .insert(rule.id, rule)
+    }
+
+    pub fn remove(&self, guild_id: GuildId, rule_id: RuleId) {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rules) = guilds.get_mut(&guild_id) {
+            rules.remove(&rule_id);
+        }
+    }
+
+    pub fn get(&self, guild_id: GuildId, rule_id: RuleId) -> Option<Rule> {
+        let guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        guilds.get(&guild_id)?.get(&rule_id).cloned()
+    }
+
+    /// Whether an execution is the first for its trigger, as Discord sends one per action.
+    pub fn first_execution(&self, key: ExecutionKey, now: NaiveDateTime) -> bool {
+        let mut executions = self.executions.lock().unwrap_or_else(|e| e.into_inner());
+        executions.retain(|_, at| now - *at <= EXECUTION_WINDOW);
+        match executions.entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+                true
+            }
+        }
+    }
+}
+
+/// Fills the cache for a guild, which needs the Manage Server permission.
+pub(super) async fn seed(ctx: &serenity::Context, data: &Data, guild_id: GuildId) {
+    match guild_id.automod_rules(&ctx.http).await {
+        Ok(rules) => data.automod.seed(guild_id, rules),
+        Err(why) => warn!("Couldn't fetch the AutoMod rules of {}: {}", guild_id, why),
+    }
+}
+
+fn rule_embed(title: &str, colour: Colour, rule: &Rule) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(title)
+        .timestamp(Timestamp::now())
+        .colour(colour)
+        .field(
+            "Rule",
+            format!("{} ({})", escape_markdown(&rule.name), rule.id),
+            false,
+        )
+        .field("Trigger", describe_trigger(rule.trigger.kind()), true)
+}
+
+/// Adds the configuration of a rule, for rules which are created or deleted.
+fn with_rule_settings(mut embed: CreateEmbed, rule: &Rule) -> CreateEmbed {
+    let state = RuleState::from_rule(rule);
+    embed = embed
+        .field("Enabled", if state.enabled { "Yes" } else { "No" }, true)
+        .field("Actions", describe_list(&state.actions), false);
+    let none = Vec::new();
+    for (name, entries) in [
+        ("Keywords", &state.keywords),
+        ("Regex Patterns", &state.regex_patterns),
+        ("Allowed", &state.allow_list),
+        ("Presets", &state.presets),
+    ] {
+        if !entries.is_empty() {
+            let entries: Vec<String> = entries.iter().cloned().collect();
+            embed = embed.field(name, describe_entries(&entries, &none), false);
+        }
+    }
+    if let Some(limit) = state.mention_limit {
+        embed = embed.field("Mention Limit", limit.to_string(), true);
+    }
+    embed
+}
+
+pub(super) async fn log_rule_create(
+    ctx: &serenity::Context,
+    data: &Data,
+    rule: &Rule,
+) -> DoomResult<()> {
+    data.automod.insert(rule.clone());
+    let embed = with_rule_settings(
+        rule_embed("AutoMod Rule Created", Colour::DARK_GREEN, rule),
+        rule,
+    );
+    send_attributed_log(
+        ctx,
+        data,
+        rule.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Created by",
+            &[audit_log::Action::AutoMod(AutoModAction::RuleCreate)],
+            rule.id.get(),
+        ),
+    )
+    .await
+}
+
+pub(super) async fn log_rule_delete(
+    ctx: &serenity::Context,
+    data: &Data,
+    rule: &Rule,
+) -> DoomResult<()> {
+    data.automod.remove(rule.guild_id, rule.id);
+    let embed = with_rule_settings(rule_embed("AutoMod Rule Deleted", Colour::RED, rule), rule);
+    send_attributed_log(
+        ctx,
+        data,
+        rule.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Deleted by",
+            &[audit_log::Action::AutoMod(AutoModAction::RuleDelete)],
+            rule.id.get(),
+        ),
+    )
+    .await
+}
+
+/// Logs what changed about a rule, compared to the cached rule.
+pub(super) async fn log_rule_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    rule: &Rule,
+) -> DoomResult<()> {
+    let Some(old) = data.automod.insert(rule.clone()) else {
+        skip_uncached_update("AutoMod rule", rule.id);
+        return Ok(());
+    };
+    let changes = detect_rule_changes(&RuleState::from_rule(&old), &RuleState::from_rule(rule));
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut embed = rule_embed("AutoMod Rule Updated", Colour::BLUE, rule);
+    for change in &changes {
+        embed = match change {
+            RuleChange::Name { before, after } => embed.field(
+                "Name",
+                format!("{} → {}", escape_markdown(before), escape_markdown(after)),
+                false,
+            ),
+            RuleChange::Enabled(enabled) => {
+                embed.field("Enabled", if *enabled { "Yes" } else { "No" }, true)
+            }
+            RuleChange::Keywords { added, removed } => {
+                embed.field("Keywords", describe_entries(added, removed), false)
+            }
+            RuleChange::RegexPatterns { added, removed } => {
+                embed.field("Regex Patterns", describe_entries(added, removed), false)
+            }
+            RuleChange::AllowList { added, removed } => {
+                embed.field("Allowed", describe_entries(added, removed), false)
+            }
+            RuleChange::Presets { added, removed } => {
+                embed.field("Presets", describe_entries(added, removed), false)
+            }
+            RuleChange::MentionLimit { before, after } => {
+                let limit =
+                    |limit: &Option<u8>| limit.map_or("*none*".to_string(), |l| l.to_string());
+                embed.field(
+                    "Mention Limit",
+                    format!("{} → {}", limit(before), limit(after)),
+                    true,
+                )
+            }
+            RuleChange::Actions { before, after } => embed.field(
+                "Actions",
+                truncate(
+                    &format!("{} → {}", describe_list(before), describe_list(after)),
+                    FIELD_LIMIT,
+                ),
+                false,
+            ),
+            RuleChange::ExemptRoles { added, removed } => {
+                embed.field("Exempt Roles", describe_mentions(added, removed), false)
+            }
+            RuleChange::ExemptChannels { added, removed } => {
+                embed.field("Exempt Channels", describe_mentions(added, removed), false)
+            }
+        };
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        rule.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Updated by",
+            &[audit_log::Action::AutoMod(AutoModAction::RuleUpdate)],
+            rule.id.get(),
+        ),
+    )
+    .await
+}
+
+/// Logs content which AutoMod blocked or flagged, recording it in the moderation history.
+/// Discord sends an execution for each action of the rule, only the first of which is logged.
+pub(super) async fn log_action_execution(
+    ctx: &serenity::Context,
+    data: &Data,
+    execution: &ActionExecution,
+) -> DoomResult<()> {
+    let guild_id = execution.guild_id;
+    let rule = data.automod.get(guild_id, execution.rule_id);
+    if let Some(first) = rule.as_ref().and_then(|rule| rule.actions.first()) {
+        if first.kind() != execution.action.kind() {
+            return Ok(());
+        }
+    }
+    // Without the rule at hand, the first action to arrive stands for all of them.
+    let now = Timestamp::now().naive_utc();
+    if !data.automod.first_execution(execution.into(), now) {
+        return Ok(());
+    }
+    let user_id = execution.user_id;
+    if let Some(channel_id) = execution.channel_id {
+        if is_ignored(ctx, data, guild_id, channel_id, Some(user_id), None) {
+            return Ok(());
+        }
+    }
+
+    // Flagged messages which weren't blocked are stored as usual.
+    let mut content = execution.content.clone();
+    if content.is_empty() {
+        if let Some(message_id) = execution.message_id {
+            let stored = data
+                .db
+                .run(move |pool| get_message_by_id(pool, message_id.get()))
+                .await?;
+            if let Some(stored) = stored {
+                content = stored.content;
+            }
+        }
+    }
+
+    let rule_name = rule.as_ref().map(|rule| rule.name.clone());
+    let reason = match (&rule_name, &execution.matched_keyword) {
+        (Some(name), Some(keyword)) => Some(format!("{} ({})", name, keyword)),
+        (Some(name), None) => Some(name.clone()),
+        (None, keyword) => keyword.clone(),
+    };
+    let action = NewModerationAction {
+        guild_id: guild_id.get() as i64,
+        user_id: user_id.get() as i64,
+        moderator_id: None,
+        kind: ModerationKind::AutoMod.key().to_string(),
+        reason,
+        created_at: Timestamp::now().naive_utc(),
+    };
+    data.db
+        .run(move |pool| record_moderation_action(pool, action))
+        .await?;
+
+    let mut embed = CreateEmbed::new()
+        .title("AutoMod Action")
+        .timestamp(Timestamp::now())
+        .colour(Colour::ORANGE)
+        .field(
+            "User",
+            format!("{} ({})", user_id.mention(), user_id),
+            false,
+        )
+        .field(
+            "Rule",
+            rule_name.map_or(execution.rule_id.to_string(), |name| escape_markdown(&name)),
+            true,
+        )
+        .field("Trigger", describe_trigger(execution.trigger_type), true);
+    if let Some(channel_id) = execution.channel_id {
+        embed = embed.field("Channel", channel_id.mention().to_string(), true);
+        if let Some(message_id) = execution.message_id {
+            embed = embed.url(construct_msg_ref(
+                guild_id.get(),
+                channel_id.get(),
+                message_id.get(),
+            ));
+        }
+    }
+    if let Some(keyword) = &execution.matched_keyword {
+        embed = embed.field("Keyword", escape_markdown(&truncate(keyword, 100)), true);
+    }
+    if let Some(matched) = execution.matched_content.as_ref().filter(|m| !m.is_empty()) {
+        embed = embed.field("Matched", escape_markdown(&truncate(matched, 100)), true);
+    }
+    let actions = match &rule {
+        Some(rule) => rule.actions.iter().map(describe_action).collect(),
+        None => vec![describe_action(&execution.action)],
+    };
+    embed = embed
+        .field("Actions", describe_list(&actions), false)
+        .field(
+            "Content",
+            if content.is_empty() {
+                "*unknown*".to_string()
+            } else {
+                truncate(&content, FIELD_LIMIT)
+            },
+            false,
+        );
+
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::Moderation,
+        CreateMessage::new().embed(embed),
+    )
+    .await
+}
@@ -0,0 +1,96 @@
+use poise::serenity_prelude::{MessageFlags, MessageUpdateEvent};
+use serde_json::Value;
+
+/// The parts of a message which can change without its content changing.
+/// `None` marks a part which is unknown, e.g. because an update didn't include it.
+#[derive(Default)]
+pub struct MessageState {
+    pub attachments: Option<Vec<u64>>,
+    pub flags: Option<MessageFlags>,
+    pub pinned: Option<bool>,
+    pub components: Option<Value>,
+}
+
+impl MessageState {
+    pub fn from_update(event: &MessageUpdateEvent) -> Self {
+        MessageState {
+            attachments: event
+                .attachments
+                .as_ref()
+                .map(|attachments| attachments.iter().map(|a| a.id.get()).collect()),
+            flags: event.flags.map(|flags| flags.unwrap_or_default()),
+            pinned: event.pinned,
+            components: event
+                .components
+                .as_ref()
+                .and_then(|components| serde_json::to_value(components).ok()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MessageChange {
+    AttachmentsRemoved(Vec<u64>),
+    EmbedsSuppressed,
+    EmbedsRestored,
+    FlagsChanged {
+        added: MessageFlags,
+        removed: MessageFlags,
+    },
+    Pinned,
+    Unpinned,
+    ComponentsChanged,
+}
+
+pub fn detect_changes(before: &MessageState, after: &MessageState) -> Vec<MessageChange> {
+    let mut changes = vec![];
+
+    if let (Some(before), Some(after)) = (&before.attachments, &after.attachments) {
+        let removed: Vec<u64> = before
+            .iter()
+            .filter(|id| !after.contains(id))
+            .copied()
+            .collect();
+        if !removed.is_empty() {
+            changes.push(MessageChange::AttachmentsRemoved(removed));
+        }
+    }
+
+    if let (Some(before), Some(after)) = (before.flags, after.flags) {
+        let suppressed = MessageFlags::SUPPRESS_EMBEDS;
+        match (before.contains(suppressed), after.contains(suppressed)) {
+            (false, true) => changes.push(MessageChange::EmbedsSuppressed),
+            (true, false) => changes.push(MessageChange::EmbedsRestored),
+            _ => {}
+        }
+        let added = after.difference(before).difference(suppressed);
+        let removed = before.difference(after).difference(suppressed);
+        if !added.is_empty() || !removed.is_empty() {
+            changes.push(MessageChange::FlagsChanged { added, removed });
+        }
+    }
+
+    if let (Some(before), Some(after)) = (before.pinned, after.pinned) {
+        match (before, after) {
+            (false, true) => changes.push(MessageChange::Pinned),
+            (true, false) => changes.push(MessageChange::Unpinned),
+            _ => {}
+        }
+    }
+
+    if let (Some(before), Some(after)) = (&before.components, &after.components) {
+        if before != after {
+            changes.push(MessageChange::ComponentsChanged);
+        }
+    }
+
+    changes
+}
+
+pub fn flag_names(flags: MessageFlags) -> String {
+    if flags.is_empty() {
+        return "-".to_string();
+    }
+    let names: Vec<_> = flags.iter_names().map(|(name, _)| name).collect();
+    names.join(", ")
+}
@@ -0,0 +1,425 @@
+use super::{send_attributed_log, skip_uncached_update, Attribution};
+use crate::config::ignore::EventScope;
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::persistence::get_channel_message_count;
+use crate::util::discord::{join_limited, permission_name, truncate_escaped, FIELD_LIMIT};
+use crate::Data;
+use poise::serenity_prelude::audit_log::{Action, ChannelAction, ChannelOverwriteAction};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, Colour, CreateEmbed, CreateMessage, GuildChannel, Mentionable,
+    PermissionOverwriteType, Permissions, RoleId, Timestamp, UserId,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Whom a permission overwrite applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OverwriteTarget {
+    Role(RoleId),
+    Member(UserId),
+}
+
+impl OverwriteTarget {
+    fn mention(self) -> String {
+        match self {
+            OverwriteTarget::Role(id) => id.mention().to_string(),
+            OverwriteTarget::Member(id) => id.mention().to_string(),
+        }
+    }
+}
+
+/// What an overwrite does with a single permission.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PermissionState {
+    Allow,
+    Inherit,
+    Deny,
+}
+
+impl PermissionState {
+    fn of(permission: Permissions, allow: Permissions, deny: Permissions) -> Self {
+        if allow.contains(permission) {
+            PermissionState::Allow
+        } else if deny.contains(permission) {
+            PermissionState::Deny
+        } else {
+            PermissionState::Inherit
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            PermissionState::Allow => "✅",
+            PermissionState::Inherit => "➖",
+            PermissionState::Deny => "❌",
+        }
+    }
+}
+
+/// The parts of a channel which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelState {
+    pub name: String,
+    pub topic: Option<String>,
+    /// Slow mode in seconds, 0 if it's off.
+    pub slowmode: u16,
+    pub nsfw: bool,
+    pub parent_id: Option<ChannelId>,
+    /// Allowed and denied permissions per target.
+    pub overwrites: BTreeMap<OverwriteTarget, (Permissions, Permissions)>,
+}
+
+impl ChannelState {
+    pub fn from_channel(channel: &GuildChannel) -> Self {
+        ChannelState {
+            name: channel.name.clone(),
+            topic: channel.topic.clone().filter(|topic| !topic.is_empty()),
+            slowmode: channel.rate_limit_per_user.unwrap_or_default(),
+            nsfw: channel.nsfw,
+            parent_id: channel.parent_id,
+            overwrites: channel
+                .permission_overwrites
+                .iter()
+                .filter_map(|overwrite| {
+                    let target = match overwrite.kind {
+                        PermissionOverwriteType::Role(id) => OverwriteTarget::Role(id),
+                        PermissionOverwriteType::Member(id) => OverwriteTarget::Member(id),
+                        _ => return None,
+                    };
+                    Some((target, (overwrite.allow, overwrite.deny)))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ChannelChange {
+    Name {
+        before: String,
+        after: String,
+    },
+    Topic {
+        before: Option<String>,
+        after: Option<String>,
+    },
+    Slowmode {
+        before: u16,
+        after: u16,
+    },
+    Nsfw(bool),
+    Parent {
+        before: Option<ChannelId>,
+        after: Option<ChannelId>,
+    },
+    /// The permissions whose state changed for a target, which may have been given an
+    /// overwrite or lost it altogether.
+    Overwrite {
+        target: OverwriteTarget,
+        added: bool,
+        removed: bool,
+        permissions: Vec<(Permissions, PermissionState, PermissionState)>,
+    },
+}
+
+impl ChannelChange {
+    fn is_overwrite(&self) -> bool {
+        matches!(self, ChannelChange::Overwrite { .. })
+    }
+}
+
+pub fn detect_channel_changes(before: &ChannelState, after: &ChannelState) -> Vec<ChannelChange> {
+    let mut changes = vec![];
+
+    if before.name != after.name {
+        changes.push(ChannelChange::Name {
+            before: before.name.clone(),
+            after: after.name.clone(),
+        });
+    }
+    if before.topic != after.topic {
+        changes.push(ChannelChange::Topic {
+            before: before.topic.clone(),
+            after: after.topic.clone(),
+        });
+    }
+    if before.slowmode != after.slowmode {
+        changes.push(ChannelChange::Slowmode {
+            before: before.slowmode,
+            after: after.slowmode,
+        });
+    }
+    if before.nsfw != after.nsfw {
+        changes.push(ChannelChange::Nsfw(after.nsfw));
+    }
+    if before.parent_id != after.parent_id {
+        changes.push(ChannelChange::Parent {
+            before: before.parent_id,
+            after: after.parent_id,
+        });
+    }
+
+    let targets: BTreeSet<OverwriteTarget> = before
+        .overwrites
+        .keys()
+        .chain(after.overwrites.keys())
+        .copied()
+        .collect();
+    for target in targets {
+        let old = before.overwrites.get(&target);
+        let new = after.overwrites.get(&target);
+        let (old_allow, old_deny) = old.copied().unwrap_or_default();
+        let (new_allow, new_deny) = new.copied().unwrap_or_default();
+        let touched = old_allow | old_deny | new_allow | new_deny;
+        let permissions: Vec<_> = touched
+            .iter()
+            .filter_map(|permission| {
+                let from = PermissionState::of(permission, old_allow, old_deny);
+                let to = PermissionState::of(permission, new_allow, new_deny);
+                (from != to).then_some((permission, from, to))
+            })
+            .collect();
+        if permissions.is_empty() && old.is_some() == new.is_some() {
+            continue;
+        }
+        changes.push(ChannelChange::Overwrite {
+            target,
+            added: old.is_none(),
+            removed: new.is_none(),
+            permissions,
+        });
+    }
+
+    changes
+}
+
+fn describe_slowmode(seconds: u16) -> String {
+    match seconds {
+        0 => "off".to_string(),
+        seconds if seconds % 3600 == 0 => format!("{}h", seconds / 3600),
+        seconds if seconds % 60 == 0 => format!("{}m", seconds / 60),
+        seconds => format!("{}s", seconds),
+    }
+}
+
+fn describe_text(text: &Option<String>, limit: usize) -> String {
+    match text {
+        Some(text) => truncate_escaped(text, limit),
+        None => "*none*".to_string(),
+    }
+}
+
+fn describe_parent(parent: Option<ChannelId>) -> String {
+    parent.map_or("*none*".to_string(), |id| id.mention().to_string())
+}
+
+fn describe_overwrite(change: &ChannelChange) -> Option<String> {
+    let ChannelChange::Overwrite {
+        target,
+        added,
+        removed,
+        permissions,
+    } = change
+    else {
+        return None;
+    };
+    let mut line = target.mention();
+    if *added {
+        line.push_str(" (added)");
+    } else if *removed {
+        line.push_str(" (removed)");
+    }
+    let permissions: Vec<String> = permissions
+        .iter()
+        .map(|(permission, from, to)| {
+            format!(
+                "{} {}→{}",
+                permission_name(*permission),
+                from.symbol(),
+                to.symbol()
+            )
+        })
+        .collect();
+    if !permissions.is_empty() {
+        line.push_str(": ");
+        line.push_str(&permissions.join(", "));
+    }
+    Some(line)
+}
+
+fn channel_embed(title: &str, colour: Colour, channel: &GuildChannel) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(title)
+        .timestamp(Timestamp::now())
+        .colour(colour)
+        .field(
+            "Channel",
+            format!("{} `#{}` ({})", channel.mention(), channel.name, channel.id),
+            false,
+        )
+        .field("Type", channel.kind.name(), true)
+}
+
+/// Deleted channels are gone from the cache already, so the scope comes from the channel itself.
+fn ignored(data: &Data, channel: &GuildChannel) -> bool {
+    let channels: Vec<ChannelId> = std::iter::once(channel.id)
+        .chain(channel.parent_id)
+        .collect();
+    data.config.is_ignored(
+        channel.guild_id,
+        &EventScope {
+            channels: &channels,
+            user: None,
+            roles: &[],
+        },
+    )
+}
+
+pub(super) async fn log_channel_create(
+    ctx: &serenity::Context,
+    data: &Data,
+    channel: &GuildChannel,
+) -> DoomResult<()> {
+    if ignored(data, channel) {
+        return Ok(());
+    }
+    let mut embed = channel_embed("Channel Created", Colour::DARK_GREEN, channel);
+    if let Some(parent) = channel.parent_id {
+        embed = embed.field("Category", parent.mention().to_string(), true);
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        channel.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Created by",
+            &[Action::Channel(ChannelAction::Create)],
+            channel.id.get(),
+        ),
+    )
+    .await
+}
+
+pub(super) async fn log_channel_delete(
+    ctx: &serenity::Context,
+    data: &Data,
+    channel: &GuildChannel,
+) -> DoomResult<()> {
+    if ignored(data, channel) {
+        return Ok(());
+    }
+    let channel_id = channel.id;
+    let stored = data
+        .db
+        .run(move |pool| get_channel_message_count(pool, channel_id.get()))
+        .await?;
+
+    let mut embed = channel_embed("Channel Deleted", Colour::RED, channel);
+    if let Some(parent) = channel.parent_id {
+        embed = embed.field("Category", parent.mention().to_string(), true);
+    }
+    embed = embed.field("Stored Messages", stored.to_string(), true);
+    send_attributed_log(
+        ctx,
+        data,
+        channel.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Deleted by",
+            &[Action::Channel(ChannelAction::Delete)],
+            channel.id.get(),
+        ),
+    )
+    .await
+}
+
+/// Logs what changed about a channel, compared to the cached channel.
+pub(super) async fn log_channel_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&GuildChannel>,
+    new: &GuildChannel,
+) -> DoomResult<()> {
+    if ignored(data, new) {
+        return Ok(());
+    }
+    let Some(old) = old else {
+        skip_uncached_update("channel", new.id);
+        return Ok(());
+    };
+    let changes = detect_channel_changes(
+        &ChannelState::from_channel(old),
+        &ChannelState::from_channel(new),
+    );
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut embed = channel_embed("Channel Updated", Colour::BLUE, new);
+    for change in &changes {
+        embed = match change {
+            ChannelChange::Name { before, after } => embed.field(
+                "Name",
+                // Markdown isn't processed inside code spans, so the names go as they are.
+                format!("`#{}` → `#{}`", before, after),
+                false,
+            ),
+            ChannelChange::Topic { before, after } => embed
+                .field("Old Topic", describe_text(before, FIELD_LIMIT), false)
+                .field("New Topic", describe_text(after, FIELD_LIMIT), false),
+            ChannelChange::Slowmode { before, after } => embed.field(
+                "Slow Mode",
+                format!(
+                    "{} → {}",
+                    describe_slowmode(*before),
+                    describe_slowmode(*after)
+                ),
+                true,
+            ),
+            ChannelChange::Nsfw(nsfw) => {
+                embed.field("NSFW", if *nsfw { "Enabled" } else { "Disabled" }, true)
+            }
+            ChannelChange::Parent { before, after } => embed.field(
+                "Category",
+                format!("{} → {}", describe_parent(*before), describe_parent(*after)),
+                true,
+            ),
+            ChannelChange::Overwrite { .. } => embed,
+        };
+    }
+    let overwrites: Vec<String> = changes.iter().filter_map(describe_overwrite).collect();
+    if !overwrites.is_empty() {
+        embed = embed.field(
+            "Permission Overwrites",
+            join_limited(&overwrites, "\n", FIELD_LIMIT),
+            false,
+        );
+    }
+
+    let mut actions = vec![];
+    if changes.iter().any(|change| !change.is_overwrite()) {
+        actions.push(Action::Channel(ChannelAction::Update));
+    }
+    if !overwrites.is_empty() {
+        actions.extend([
+            Action::ChannelOverwrite(ChannelOverwriteAction::Update),
+            Action::ChannelOverwrite(ChannelOverwriteAction::Create),
+            Action::ChannelOverwrite(ChannelOverwriteAction::Delete),
+        ]);
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        new.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new("Updated by", &actions, new.id.get()),
+    )
+    .await
+}
@@ -0,0 +1,208 @@
+use crate::persistence::models::NewInviteUse;
+use crate::persistence::record_invite_use;
+use crate::Data;
+use chrono::{NaiveDateTime, TimeDelta};
+use log::warn;
+use poise::serenity_prelude::{
+    self as serenity, GuildId, InviteCreateEvent, Mentionable, RichInvite, Timestamp, UserId,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// What is needed of an invite to tell which one a member joined through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackedInvite {
+    pub inviter: Option<UserId>,
+    pub uses: u64,
+    /// Zero for unlimited invites.
+    pub max_uses: u64,
+}
+
+impl From<&RichInvite> for TrackedInvite {
+    fn from(invite: &RichInvite) -> Self {
+        TrackedInvite {
+            inviter: invite.inviter.as_ref().map(|user| user.id),
+            uses: invite.uses,
+            max_uses: u64::from(invite.max_uses),
+        }
+    }
+}
+
+impl From<&InviteCreateEvent> for TrackedInvite {
+    fn from(invite: &InviteCreateEvent) -> Self {
+        TrackedInvite {
+            inviter: invite.inviter.as_ref().map(|user| user.id),
+            uses: invite.uses,
+            max_uses: u64::from(invite.max_uses),
+        }
+    }
+}
+
+pub type Invites = BTreeMap<String, TrackedInvite>;
+
+pub fn invite_map(invites: &[RichInvite]) -> Invites {
+    invites
+        .iter()
+        .map(|invite| (invite.code.clone(), invite.into()))
+        .collect()
+}
+
+/// Finds the invite a member joined through by comparing use counts. An invite which vanished
+/// with its last use counts too, as Discord deletes those right away, often announcing the
+/// deletion before the join, hence the `deleted` ones. Gives up when ambiguous.
+pub fn attribute_join(before: &Invites, after: &Invites, deleted: &Invites) -> Option<String> {
+    let used: Vec<&String> = after
+        .iter()
+        .filter(|(code, invite)| {
+            let previous = before.get(*code).map_or(0, |previous| previous.uses);
+            invite.uses > previous
+        })
+        .map(|(code, _)| code)
+        .collect();
+    if let [code] = used.as_slice() {
+        return Some(code.to_string());
+    }
+    if !used.is_empty() {
+        return None;
+    }
+
+    let exhausted: Vec<&String> = before
+        .iter()
+        .chain(
+            deleted
+                .iter()
+                .filter(|(code, _)| !before.contains_key(*code)),
+        )
+        .filter(|(code, invite)| {
+            !after.contains_key(*code) && invite.max_uses != 0 && invite.uses + 1 >= invite.max_uses
+        })
+        .map(|(code, _)| code)
+        .collect();
+    match exhausted.as_slice() {
+        [code] => Some(code.to_string()),
+        _ => None,
+    }
+}
+
+/// Deleted invites with when they were deleted.
+type DeletedInvites = BTreeMap<String, (TrackedInvite, NaiveDateTime)>;
+
+/// How long deleted invites are kept around for the join which used them up.
+const DELETED_INVITE_WINDOW: TimeDelta = TimeDelta::seconds(30);
+
+/// The invites of each guild with their use counts, as Discord doesn't say which one was used.
+#[derive(Default)]
+pub struct InviteCache {
+    guilds: Mutex<HashMap<GuildId, Invites>>,
+    deleted: Mutex<HashMap<GuildId, DeletedInvites>>,
+}
+
+impl InviteCache {
+    /// Stores the current invites, returning the previous ones if known.
+    pub fn replace(&self, guild_id: GuildId, invites: Invites) -> Option<Invites> {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        guilds.insert(guild_id, invites)
+    }
+
+    pub fn insert(&self, guild_id: GuildId, code: String, invite: TrackedInvite) {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(invites) = guilds.get_mut(&guild_id) {
+            invites.insert(code, invite);
+        }
+    }
+
+    /// Removes a deleted invite, keeping it a little longer in case a join used it up.
+    pub fn remove(&self, guild_id: GuildId, code: &str, at: NaiveDateTime) {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(invite) = guilds.get_mut(&guild_id).and_then(|i| i.remove(code)) else {
+            return;
+        };
+        let mut deleted = self.deleted.lock().unwrap_or_else(|e| e.into_inner());
+        deleted
+            .entry(guild_id)
+            .or_default()
+            .insert(code.to_string(), (invite, at));
+    }
+
+    /// The invites deleted shortly before `now`, dropping older ones.
+    pub fn recently_deleted(&self, guild_id: GuildId, now: NaiveDateTime) -> Invites {
+        let mut deleted = self.deleted.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(invites) = deleted.get_mut(&guild_id) else {
+            return Invites::new();
+        };
+        invites.retain(|_, (_, at)| now - *at <= DELETED_INVITE_WINDOW);
+        invites
+            .iter()
+            .map(|(code, (invite, _))| (code.clone(), invite.clone()))
+            .collect()
+    }
+
+    /// Drops a deleted invite once a join was attributed to it.
+    pub fn forget_deleted(&self, guild_id: GuildId, code: &str) {
+        let mut deleted = self.deleted.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(invites) = deleted.get_mut(&guild_id) {
+            invites.remove(code);
+        }
+    }
+}
+
+async fn fetch_invites(ctx: &serenity::Context, guild_id: GuildId) -> Option<Invites> {
+    match guild_id.invites(&ctx.http).await {
+        Ok(invites) => Some(invite_map(&invites)),
+        Err(why) => {
+            warn!("Couldn't fetch the invites of {}: {}", guild_id, why);
+            None
+        }
+    }
+}
+
+/// Fills the cache for a guild, which needs the Manage Server permission.
+pub(super) async fn seed(ctx: &serenity::Context, data: &Data, guild_id: GuildId) {
+    if let Some(invites) = fetch_invites(ctx, guild_id).await {
+        data.invites.replace(guild_id, invites);
+    }
+}
+
+/// Works out which invite a member joined through and records its use, returning a description.
+pub(super) async fn track_join(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Option<String> {
+    let current = fetch_invites(ctx, guild_id).await?;
+    let previous = data.invites.replace(guild_id, current.clone())?;
+    let now = Timestamp::now().naive_utc();
+    let deleted = data.invites.recently_deleted(guild_id, now);
+    let code = attribute_join(&previous, &current, &deleted)?;
+    data.invites.forget_deleted(guild_id, &code);
+    let inviter = current
+        .get(&code)
+        .or_else(|| previous.get(&code))
+        .or_else(|| deleted.get(&code))
+        .and_then(|invite| invite.inviter);
+
+    let invite_use = NewInviteUse {
+        guild_id: guild_id.get() as i64,
+        user_id: user_id.get() as i64,
+        code: code.clone(),
+        inviter_id: inviter.map(|inviter| inviter.get() as i64),
+        used_at: now,
+    };
+    if let Err(why) = data
+        .db
+        .run(move |pool| record_invite_use(pool, invite_use))
+        .await
+    {
+        warn!("Couldn't record the use of invite {}: {}", code, why);
+    }
+
+    Some(match inviter {
+        Some(inviter) => format!(
+            "Joined via discord.gg/{} (created by {})",
+            code,
+            inviter.mention()
+        ),
+        None => format!("Joined via discord.gg/{}", code),
+    })
+}
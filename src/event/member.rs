@@ -0,0 +1,442 @@
+use super::{invite, send_attributed_log, send_log, Attribution};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::persistence::models::MemberSnapshot;
+use crate::persistence::{
+    get_member_history, get_member_snapshot, record_member_join, record_member_leave,
+    save_member_snapshot,
+};
+use crate::util::discord::{
+    format_relative, format_timestamp, join_limited, truncate_escaped, FIELD_LIMIT,
+};
+use crate::Data;
+use chrono::{NaiveDateTime, TimeDelta};
+use poise::serenity_prelude::audit_log::{Action, MemberAction};
+use poise::serenity_prelude::{
+    self as serenity, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId,
+    GuildMemberUpdateEvent, Member, Mentionable, RoleId, Timestamp, User, UserId,
+};
+use std::collections::BTreeSet;
+
+/// The parts of a member which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemberState {
+    pub nick: Option<String>,
+    /// Hash of the server avatar.
+    pub avatar: Option<String>,
+    pub roles: BTreeSet<RoleId>,
+    pub timeout_until: Option<NaiveDateTime>,
+}
+
+impl MemberState {
+    pub fn from_member(member: &Member) -> Self {
+        MemberState {
+            nick: member.nick.clone(),
+            avatar: member.avatar.map(|hash| hash.to_string()),
+            roles: member.roles.iter().copied().collect(),
+            timeout_until: member.communication_disabled_until.map(|t| t.naive_utc()),
+        }
+    }
+
+    pub fn from_update(event: &GuildMemberUpdateEvent) -> Self {
+        MemberState {
+            nick: event.nick.clone(),
+            avatar: event.avatar.map(|hash| hash.to_string()),
+            roles: event.roles.iter().copied().collect(),
+            timeout_until: event.communication_disabled_until.map(|t| t.naive_utc()),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: MemberSnapshot) -> Self {
+        MemberState {
+            nick: snapshot.nick,
+            avatar: snapshot.avatar,
+            roles: snapshot
+                .roles
+                .split_whitespace()
+                .filter_map(|id| id.parse().ok())
+                .filter(|id| *id != 0)
+                .map(RoleId::new)
+                .collect(),
+            timeout_until: snapshot.timeout_until,
+        }
+    }
+
+    pub fn to_snapshot(&self, guild_id: GuildId, user_id: UserId) -> MemberSnapshot {
+        MemberSnapshot {
+            guild_id: guild_id.get() as i64,
+            user_id: user_id.get() as i64,
+            nick: self.nick.clone(),
+            avatar: self.avatar.clone(),
+            roles: self
+                .roles
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            timeout_until: self.timeout_until,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MemberChange {
+    RolesAdded(Vec<RoleId>),
+    RolesRemoved(Vec<RoleId>),
+    Nickname {
+        before: Option<String>,
+        after: Option<String>,
+    },
+    Avatar {
+        before: Option<String>,
+        after: Option<String>,
+    },
+    TimedOut(NaiveDateTime),
+    TimeoutRemoved,
+}
+
+impl MemberChange {
+    /// Role changes are logged as a different audit log action than the rest.
+    fn is_role_change(&self) -> bool {
+        matches!(
+            self,
+            MemberChange::RolesAdded(_) | MemberChange::RolesRemoved(_)
+        )
+    }
+
+    fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            MemberChange::TimedOut(_) | MemberChange::TimeoutRemoved
+        )
+    }
+}
+
+/// Diffs two states of a member. Timeouts only count while they last, as Discord doesn't
+/// clear the timestamp when they run out.
+pub fn detect_member_changes(
+    before: &MemberState,
+    after: &MemberState,
+    now: NaiveDateTime,
+) -> Vec<MemberChange> {
+    let mut changes = vec![];
+
+    let added: Vec<RoleId> = after.roles.difference(&before.roles).copied().collect();
+    if !added.is_empty() {
+        changes.push(MemberChange::RolesAdded(added));
+    }
+    let removed: Vec<RoleId> = before.roles.difference(&after.roles).copied().collect();
+    if !removed.is_empty() {
+        changes.push(MemberChange::RolesRemoved(removed));
+    }
+
+    if before.nick != after.nick {
+        changes.push(MemberChange::Nickname {
+            before: before.nick.clone(),
+            after: after.nick.clone(),
+        });
+    }
+    if before.avatar != after.avatar {
+        changes.push(MemberChange::Avatar {
+            before: before.avatar.clone(),
+            after: after.avatar.clone(),
+        });
+    }
+
+    let active = |until: Option<NaiveDateTime>| until.filter(|until| *until > now);
+    match (active(before.timeout_until), active(after.timeout_until)) {
+        (before, Some(until)) if before != Some(until) => {
+            changes.push(MemberChange::TimedOut(until))
+        }
+        (Some(_), None) => changes.push(MemberChange::TimeoutRemoved),
+        _ => {}
+    }
+
+    changes
+}
+
+fn server_avatar_url(guild_id: GuildId, user_id: UserId, hash: &str) -> String {
+    let extension = if hash.starts_with("a_") {
+        "gif"
+    } else {
+        "webp"
+    };
+    format!(
+        "https://cdn.discordapp.com/guilds/{}/users/{}/avatars/{}.{}?size=1024",
+        guild_id, user_id, hash, extension
+    )
+}
+
+fn member_count(ctx: &serenity::Context, guild_id: GuildId) -> Option<u64> {
+    ctx.cache.guild(guild_id).map(|guild| guild.member_count)
+}
+
+fn base_embed(title: &str, colour: Colour, user: &User) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(title)
+        .timestamp(Timestamp::now())
+        .colour(colour)
+        .thumbnail(user.face())
+        .field("Member", format!("{} ({})", user.mention(), user.id), false)
+        .footer(CreateEmbedFooter::new(&user.name).icon_url(user.face()))
+}
+
+pub(super) async fn log_member_addition(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> DoomResult<()> {
+    let (guild_id, user_id) = (member.guild_id, member.user.id);
+    let now = Timestamp::now().naive_utc();
+    let history = data
+        .db
+        .run(move |pool| get_member_history(pool, guild_id.get(), user_id.get()))
+        .await?;
+    let joined_at = member.joined_at.map_or(now, |at| at.naive_utc());
+    data.db
+        .run(move |pool| record_member_join(pool, guild_id.get(), user_id.get(), joined_at))
+        .await?;
+    let snapshot = MemberState::from_member(member).to_snapshot(guild_id, user_id);
+    data.db
+        .run(move |pool| save_member_snapshot(pool, snapshot))
+        .await?;
+
+    let joined_via = invite::track_join(ctx, data, guild_id, user_id).await;
+
+    let created_at = user_id.created_at().naive_utc();
+    let threshold = TimeDelta::days(i64::from(data.config.get(guild_id).new_account_days));
+    let new_account = now - created_at < threshold;
+
+    let mut embed = base_embed(
+        "Member Joined",
+        if new_account {
+            Colour::ORANGE
+        } else {
+            Colour::DARK_GREEN
+        },
+        &member.user,
+    )
+    .field(
+        "Account Created",
+        format!(
+            "{} ({})",
+            format_timestamp(created_at),
+            format_relative(created_at)
+        ),
+        true,
+    );
+    if let Some(count) = member_count(ctx, guild_id) {
+        embed = embed.field("Member Count", count.to_string(), true);
+    }
+    if let Some(joined_via) = joined_via {
+        embed = embed.field("Invite", joined_via, false);
+    }
+    if new_account {
+        embed = embed.field(
+            ":warning: New Account",
+            format!("Created less than {} days ago.", threshold.num_days()),
+            false,
+        );
+    }
+    if !history.is_empty() {
+        let last_left = history.iter().rev().find_map(|stint| stint.left_at);
+        embed = embed.field(
+            ":repeat: Rejoined",
+            match last_left {
+                Some(left_at) => format!(
+                    "Joined {} time(s) before, last left {}.",
+                    history.len(),
+                    format_relative(left_at)
+                ),
+                None => format!("Joined {} time(s) before.", history.len()),
+            },
+            false,
+        );
+    }
+
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::MemberEvents,
+        CreateMessage::new().embed(embed),
+    )
+    .await
+}
+
+pub(super) async fn log_member_removal(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user: &User,
+    member: Option<&Member>,
+) -> DoomResult<()> {
+    let user_id = user.id;
+    let now = Timestamp::now().naive_utc();
+    let joined = member.and_then(|m| m.joined_at).map(|at| at.naive_utc());
+    data.db
+        .run(move |pool| record_member_leave(pool, guild_id.get(), user_id.get(), joined, now))
+        .await?;
+    let joined = match joined {
+        Some(joined) => Some(joined),
+        None => data
+            .db
+            .run(move |pool| get_member_history(pool, guild_id.get(), user_id.get()))
+            .await?
+            .last()
+            .and_then(|stint| stint.joined_at),
+    };
+
+    let mut embed = base_embed("Member Left", Colour::DARK_GREY, user);
+    if let Some(joined) = joined {
+        embed = embed.field(
+            "Joined",
+            format!("{} ({})", format_timestamp(joined), format_relative(joined)),
+            true,
+        );
+    }
+    if let Some(count) = member_count(ctx, guild_id) {
+        embed = embed.field("Member Count", count.to_string(), true);
+    }
+    if let Some(member) = member {
+        if !member.roles.is_empty() {
+            let roles: Vec<String> = member
+                .roles
+                .iter()
+                .map(|role| role.mention().to_string())
+                .collect();
+            embed = embed.field("Roles", join_limited(&roles, " ", FIELD_LIMIT), false);
+        }
+    }
+
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::MemberEvents,
+        CreateMessage::new().embed(embed),
+    )
+    .await
+}
+
+fn describe_nick(nick: &Option<String>) -> String {
+    match nick {
+        Some(nick) => truncate_escaped(nick, 256),
+        None => "*none*".to_string(),
+    }
+}
+
+fn mention_roles(roles: &[RoleId]) -> String {
+    let roles: Vec<String> = roles.iter().map(|id| id.mention().to_string()).collect();
+    join_limited(&roles, " ", FIELD_LIMIT)
+}
+
+/// Logs what changed about a member, comparing against the cached member or the last snapshot.
+pub(super) async fn log_member_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&Member>,
+    event: &GuildMemberUpdateEvent,
+) -> DoomResult<()> {
+    let (guild_id, user_id) = (event.guild_id, event.user.id);
+    let after = MemberState::from_update(event);
+    let before = match old {
+        Some(old) => Some(MemberState::from_member(old)),
+        None => data
+            .db
+            .run(move |pool| get_member_snapshot(pool, guild_id.get(), user_id.get()))
+            .await?
+            .map(MemberState::from_snapshot),
+    };
+    let snapshot = after.to_snapshot(guild_id, user_id);
+    data.db
+        .run(move |pool| save_member_snapshot(pool, snapshot))
+        .await?;
+
+    let Some(before) = before else {
+        return Ok(());
+    };
+    let changes = detect_member_changes(&before, &after, Timestamp::now().naive_utc());
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let timeout = changes.iter().any(MemberChange::is_timeout);
+    let mut embed = base_embed(
+        "Member Updated",
+        if timeout {
+            Colour::ORANGE
+        } else {
+            Colour::BLUE
+        },
+        &event.user,
+    );
+    for change in &changes {
+        embed = match change {
+            MemberChange::RolesAdded(roles) => {
+                embed.field("Roles Added", mention_roles(roles), false)
+            }
+            MemberChange::RolesRemoved(roles) => {
+                embed.field("Roles Removed", mention_roles(roles), false)
+            }
+            MemberChange::Nickname { before, after } => embed.field(
+                "Nickname",
+                format!("{} → {}", describe_nick(before), describe_nick(after)),
+                false,
+            ),
+            MemberChange::Avatar { before, after } => {
+                let link = |label: &str, hash: &Option<String>| match hash {
+                    Some(hash) => format!(
+                        "[{}]({})",
+                        label,
+                        server_avatar_url(guild_id, user_id, hash)
+                    ),
+                    None => "*none*".to_string(),
+                };
+                let embed = embed.field(
+                    "Server Avatar",
+                    format!("{} → {}", link("Before", before), link("After", after)),
+                    false,
+                );
+                match after {
+                    Some(hash) => embed.thumbnail(server_avatar_url(guild_id, user_id, hash)),
+                    None => embed,
+                }
+            }
+            MemberChange::TimedOut(until) => embed.field(
+                ":mute: Timed Out",
+                format!(
+                    "Until {} ({})",
+                    format_timestamp(*until),
+                    format_relative(*until)
+                ),
+                false,
+            ),
+            MemberChange::TimeoutRemoved => {
+                embed.field(":speaker: Timeout Removed", "Before it ran out.", false)
+            }
+        };
+    }
+
+    let mut actions = vec![];
+    if changes.iter().any(MemberChange::is_role_change) {
+        actions.push(Action::Member(MemberAction::RoleUpdate));
+    }
+    if changes.iter().any(|change| !change.is_role_change()) {
+        actions.push(Action::Member(MemberAction::Update));
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        guild_id,
+        if timeout {
+            LogCategory::Moderation
+        } else {
+            LogCategory::MemberEvents
+        },
+        CreateMessage::new(),
+        embed,
+        Attribution::new("Updated by", &actions, user_id.get()),
+    )
+    .await
+}
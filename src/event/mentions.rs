@@ -0,0 +1,88 @@
+use poise::serenity_prelude::{Mentionable, RoleId, UserId};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+
+static CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```.*?```|`[^`]*`").expect("Valid Regex!"));
+static MENTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<@(?<kind>[!&]?)(?<id>\d+)>|@(?<everyone>everyone|here)").expect("Valid Regex!")
+});
+
+/// Who a message pings.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Mentions {
+    pub users: BTreeSet<UserId>,
+    pub roles: BTreeSet<RoleId>,
+    pub everyone: bool,
+    pub here: bool,
+}
+
+impl Mentions {
+    /// Collects the mentions of a message, skipping code which Discord doesn't ping from.
+    pub fn parse(content: &str) -> Self {
+        let content = CODE.replace_all(content, "");
+        let mut mentions = Mentions::default();
+        for capture in MENTION.captures_iter(&content) {
+            if let Some(everyone) = capture.name("everyone") {
+                match everyone.as_str() {
+                    "everyone" => mentions.everyone = true,
+                    _ => mentions.here = true,
+                }
+                continue;
+            }
+            let Ok(id) = capture["id"].parse::<u64>() else {
+                continue;
+            };
+            if id == 0 {
+                continue;
+            }
+            match &capture["kind"] {
+                "&" => mentions.roles.insert(RoleId::new(id)),
+                _ => mentions.users.insert(UserId::new(id)),
+            };
+        }
+        mentions
+    }
+
+    /// The mentions which are gone in `after`.
+    pub fn removed(&self, after: &Mentions) -> Mentions {
+        Mentions {
+            users: self.users.difference(&after.users).copied().collect(),
+            roles: self.roles.difference(&after.roles).copied().collect(),
+            everyone: self.everyone && !after.everyone,
+            here: self.here && !after.here,
+        }
+    }
+
+    pub fn intersection(&self, other: &Mentions) -> Mentions {
+        Mentions {
+            users: self.users.intersection(&other.users).copied().collect(),
+            roles: self.roles.intersection(&other.roles).copied().collect(),
+            everyone: self.everyone && other.everyone,
+            here: self.here && other.here,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty() && self.roles.is_empty() && !self.everyone && !self.here
+    }
+
+    /// Lists the mentions, rendered so they don't ping again.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        if self.everyone {
+            parts.push("@\u{200b}everyone".to_string());
+        }
+        if self.here {
+            parts.push("@\u{200b}here".to_string());
+        }
+        parts.extend(self.roles.iter().map(|id| id.mention().to_string()));
+        parts.extend(
+            self.users
+                .iter()
+                .map(|id| format!("{} ({})", id.mention(), id)),
+        );
+        parts.join("\n")
+    }
+}
@@ -1,16 +1,55 @@
+pub(crate) mod automod;
+pub(crate) mod changes;
+pub(crate) mod channel;
+pub(crate) mod invite;
+pub(crate) mod member;
+pub(crate) mod mentions;
+pub(crate) mod moderation;
+pub(crate) mod pins;
+pub(crate) mod role;
+pub(crate) mod server;
+pub(crate) mod thread;
+pub(crate) mod voice;
+
 use crate::{Data, Error};
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 
+use crate::archive::UPLOAD_LIMIT;
+use crate::audit::{self, deleted_by_author, AuditEntry, AUDIT_LOG_DELAY};
+use crate::config::ignore::EventScope;
+use crate::config::LogCategory;
+use crate::diff;
+use crate::error::DoomResult;
+use crate::persistence::models::{Attachment, Message};
 use crate::persistence::{
-    create_message, exists_message, get_author_from_message, get_message_content_and_author_by_id,
-    get_message_content_by_id, get_message_count, update_message_content,
+    add_message_revision, create_attachment, get_attachments_by_message, get_author_from_message,
+    get_message_by_id, get_message_count, get_message_revisions, get_messages_by_ids,
+    mark_attachments_removed, update_message_state,
 };
+use crate::transcript;
+use crate::util::discord::{format_timestamp, join_limited, truncate, FIELD_LIMIT};
 use crate::util::UNKNOWN_USER;
+use changes::{detect_changes, flag_names, MessageChange, MessageState};
+use chrono::TimeDelta;
+use mentions::Mentions;
+use moderation::ModeratorAction;
 use poise::serenity_prelude::{
-    self as serenity, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, Mentionable,
-    Timestamp, UserId,
+    self as serenity, ChannelId, Colour, CreateAttachment, CreateEmbed, CreateEmbedFooter,
+    CreateMessage, EditMessage, GuildId, Mentionable, MessageFlags, MessageId, MessageUpdateEvent,
+    RoleId, Timestamp, User, UserId,
 };
+use serenity::audit_log::Action;
 use serenity::FullEvent;
+use server::AssetKind;
+use std::collections::{BTreeSet, HashMap};
+use tokio::task::JoinHandle;
+
+/// Discord's limit on the number of files per message.
+const MAX_FILES: usize = 10;
+/// How long after posting removing a mention counts as a ghost ping.
+const GHOST_PING_WINDOW: TimeDelta = TimeDelta::minutes(15);
+/// Caps the DMs per ghost ping, as a single message can mention lots of users.
+const MAX_GHOST_PING_DMS: usize = 5;
 
 fn construct_msg_ref(guild_id: u64, channel_id: u64, message_id: u64) -> String {
     format!(
@@ -19,63 +58,838 @@ fn construct_msg_ref(guild_id: u64, channel_id: u64, message_id: u64) -> String
     )
 }
 
+/// Sends a log message to the channel its category is routed to in the guild, if there is one.
+async fn send_log(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: Option<GuildId>,
+    category: LogCategory,
+    message: CreateMessage,
+) -> DoomResult<()> {
+    post_log(ctx, data, guild_id, category, message).await?;
+    Ok(())
+}
+
+/// Like `send_log`, handing out the sent message.
+async fn post_log(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: Option<GuildId>,
+    category: LogCategory,
+    message: CreateMessage,
+) -> DoomResult<Option<serenity::Message>> {
+    match data.config.log_channel(guild_id, category) {
+        Some(channel) => Ok(Some(channel.send_message(&ctx.http, message).await?)),
+        None => {
+            debug!("No channel configured for {:?} in {:?}", category, guild_id);
+            Ok(None)
+        }
+    }
+}
+
+/// Where to look for who caused a logged event: the audit log actions it may be logged as,
+/// in order, and the id they target.
+pub(super) struct Attribution {
+    label: &'static str,
+    actions: Vec<Action>,
+    target: u64,
+}
+
+impl Attribution {
+    pub(super) fn new(label: &'static str, actions: &[Action], target: u64) -> Self {
+        Attribution {
+            label,
+            actions: actions.to_vec(),
+            target,
+        }
+    }
+}
+
+/// Sends a log embed right away and adds who caused the event once the audit log has it, so
+/// waiting for the audit log doesn't hold up the events after it.
+async fn send_attributed_log(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    category: LogCategory,
+    message: CreateMessage,
+    embed: CreateEmbed,
+    attribution: Attribution,
+) -> DoomResult<()> {
+    let message = message.embed(embed.clone());
+    let Some(sent) = post_log(ctx, data, Some(guild_id), category, message).await? else {
+        return Ok(());
+    };
+    if !can_view_audit_log(ctx, guild_id) {
+        return Ok(());
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let (actions, target) = (&attribution.actions, attribution.target);
+        if let Some(entry) = find_actor(&ctx, guild_id, actions, target).await {
+            attribute(
+                &ctx,
+                sent,
+                with_actor(embed, attribution.label, Some(entry)),
+            )
+            .await;
+        }
+    });
+    Ok(())
+}
+
+/// Like `send_attributed_log`, handing out the lookup, which resolves to the entry found. The
+/// lookup runs even if the category isn't routed anywhere.
+async fn post_attributed_log(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    category: LogCategory,
+    message: CreateMessage,
+    embed: CreateEmbed,
+    attribution: Attribution,
+) -> DoomResult<JoinHandle<Option<AuditEntry>>> {
+    let message = message.embed(embed.clone());
+    let sent = post_log(ctx, data, Some(guild_id), category, message).await?;
+    let ctx = ctx.clone();
+    Ok(tokio::spawn(async move {
+        let (actions, target) = (&attribution.actions, attribution.target);
+        let entry = find_actor(&ctx, guild_id, actions, target).await?;
+        if let Some(sent) = sent {
+            let embed = with_actor(embed, attribution.label, Some(entry.clone()));
+            attribute(&ctx, sent, embed).await;
+        }
+        Some(entry)
+    }))
+}
+
+fn with_deleter(mut embed: CreateEmbed, entry: &AuditEntry) -> CreateEmbed {
+    embed = embed.field(
+        "Deleted by",
+        format!("{} ({})", entry.user_id.mention(), entry.user_id),
+        false,
+    );
+    if let Some(reason) = &entry.reason {
+        embed = embed.field("Reason", truncate(reason, FIELD_LIMIT), false);
+    }
+    embed
+}
+
+/// Replaces the embed of a sent log message with its attributed version.
+async fn attribute(ctx: &serenity::Context, mut sent: serenity::Message, embed: CreateEmbed) {
+    if let Err(why) = sent.edit(&ctx.http, EditMessage::new().embed(embed)).await {
+        warn!("Couldn't attribute log message {}: {}", sent.id, why);
+    }
+}
+
+/// The channel followed by its parents, i.e. thread, channel and category, as far as cached.
+fn channel_scope(
+    ctx: &serenity::Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Vec<ChannelId> {
+    let mut scope = vec![channel_id];
+    if let Some(guild) = ctx.cache.guild(guild_id) {
+        while scope.len() < 3 {
+            let current = scope[scope.len() - 1];
+            let parent = guild
+                .channels
+                .get(&current)
+                .or_else(|| guild.threads.iter().find(|thread| thread.id == current))
+                .and_then(|channel| channel.parent_id);
+            match parent {
+                Some(parent) => scope.push(parent),
+                None => break,
+            }
+        }
+    }
+    scope
+}
+
+/// Whether the events of a user in a channel are covered by the ignore rules of the guild.
+/// Without `roles` at hand, the ones of the cached member are used.
+fn is_ignored(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    user_id: Option<UserId>,
+    roles: Option<&[RoleId]>,
+) -> bool {
+    let channels = channel_scope(ctx, guild_id, channel_id);
+    let roles = match roles {
+        Some(roles) => roles.to_vec(),
+        None => user_id
+            .and_then(|user_id| {
+                let guild = ctx.cache.guild(guild_id)?;
+                guild.members.get(&user_id).map(|m| m.roles.clone())
+            })
+            .unwrap_or_default(),
+    };
+    data.config.is_ignored(
+        guild_id,
+        &EventScope {
+            channels: &channels,
+            user: user_id,
+            roles: &roles,
+        },
+    )
+}
+
+/// Whether the bot may view the audit log of the guild. Lookups without the permission would
+/// only fail, so they are skipped unless the cache can't tell.
+fn can_view_audit_log(ctx: &serenity::Context, guild_id: GuildId) -> bool {
+    let bot_id = ctx.cache.current_user().id;
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return true;
+    };
+    guild
+        .members
+        .get(&bot_id)
+        .is_none_or(|bot| guild.member_permissions(bot).view_audit_log())
+}
+
+/// Looks up the moderator who deleted a message of `author` in the audit log.
+async fn find_deleter(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    author: UserId,
+) -> Option<AuditEntry> {
+    if !can_view_audit_log(ctx, guild_id) {
+        return None;
+    }
+    tokio::time::sleep(AUDIT_LOG_DELAY).await;
+    let now = Timestamp::now().naive_utc();
+    match data
+        .deletions
+        .find_deleter(&*ctx.http, guild_id, channel_id, author, now)
+        .await
+    {
+        Ok(entry) => entry,
+        Err(why) => {
+            warn!("Couldn't look up the deleter in {}: {}", guild_id, why);
+            None
+        }
+    }
+}
+
+/// Looks up who caused an event about `target`, trying the actions it may be logged as in order.
+async fn find_actor(
+    ctx: &serenity::Context,
+    guild_id: GuildId,
+    actions: &[Action],
+    target: u64,
+) -> Option<AuditEntry> {
+    if !can_view_audit_log(ctx, guild_id) {
+        return None;
+    }
+    tokio::time::sleep(AUDIT_LOG_DELAY).await;
+    let now = Timestamp::now().naive_utc();
+    for action in actions {
+        match audit::find_actor(&*ctx.http, guild_id, *action, target, now).await {
+            Ok(Some(entry)) => return Some(entry),
+            Ok(None) => {}
+            Err(why) => {
+                warn!("Couldn't look up the audit log of {}: {}", guild_id, why);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Updates are logged as a diff against the cached state. Without it, there's nothing to tell
+/// which of the changes are worth logging, so the update is skipped.
+fn skip_uncached_update(kind: &str, id: impl std::fmt::Display) {
+    debug!(
+        "Skipping the update of {} {}, its previous state isn't cached.",
+        kind, id
+    );
+}
+
+/// Adds who caused an event, and why, to its embed.
+fn with_actor(embed: CreateEmbed, label: &str, entry: Option<AuditEntry>) -> CreateEmbed {
+    let Some(entry) = entry else {
+        return embed;
+    };
+    let embed = embed.field(
+        label,
+        format!("{} ({})", entry.user_id.mention(), entry.user_id),
+        true,
+    );
+    match entry.reason {
+        Some(reason) => embed.field("Reason", truncate(&reason, FIELD_LIMIT), true),
+        None => embed,
+    }
+}
+
+/// Alerts about mentions which were deleted or edited away from a message shortly after it
+/// was posted, which still pinged their targets. `new_content` is `None` for deletions.
+async fn check_ghost_ping(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    stored: &Message,
+    new_content: Option<&str>,
+) -> DoomResult<()> {
+    let now = Timestamp::now().naive_utc();
+    let flags = MessageFlags::from_bits_truncate(stored.flags as u64);
+    if now - stored.created_at > GHOST_PING_WINDOW
+        || flags.contains(MessageFlags::SUPPRESS_NOTIFICATIONS)
+    {
+        return Ok(());
+    }
+
+    // Mentions which were edited into the message never pinged anyone.
+    let pinged = if stored.edited_at.is_some() {
+        let message_id = stored.id as u64;
+        let revisions = data
+            .db
+            .run(move |pool| get_message_revisions(pool, message_id))
+            .await?;
+        revisions.first().map_or(Mentions::default(), |original| {
+            Mentions::parse(&original.content)
+        })
+    } else {
+        Mentions::parse(&stored.content)
+    };
+    // Mentions removed by an earlier edit were already alerted about.
+    let mut removed = pinged
+        .removed(&Mentions::parse(new_content.unwrap_or_default()))
+        .intersection(&Mentions::parse(&stored.content));
+    let author = UserId::new(stored.author as u64);
+    removed.users.remove(&author);
+    if removed.is_empty() {
+        return Ok(());
+    }
+
+    let channel_id = ChannelId::new(stored.channel_id.unwrap_or_default() as u64);
+    let link = construct_msg_ref(guild_id.get(), channel_id.get(), stored.id as u64);
+    let embed = CreateEmbed::new()
+        .title(match new_content {
+            Some(_) => ":rotating_light: Ghost Ping (edited)",
+            None => ":rotating_light: Ghost Ping (deleted)",
+        })
+        .url(&link)
+        .timestamp(Timestamp::now())
+        .colour(Colour::RED)
+        .field("Author", format!("{} ({})", author.mention(), author), true)
+        .field("Channel", channel_id.mention().to_string(), true)
+        .field("Posted", format_timestamp(stored.created_at), true)
+        .field("Pinged", truncate(&removed.describe(), FIELD_LIMIT), false)
+        .field("Message", truncate(&stored.content, FIELD_LIMIT), false);
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::GhostPings,
+        CreateMessage::new().embed(embed),
+    )
+    .await?;
+
+    if !data.config.get(guild_id).notify_ghost_pings {
+        return Ok(());
+    }
+    let guild_name = guild_id.name(ctx).unwrap_or_else(|| "a server".to_string());
+    for user_id in removed.users.iter().take(MAX_GHOST_PING_DMS) {
+        let notice = CreateEmbed::new()
+            .title("You were ghost pinged")
+            .colour(Colour::RED)
+            .description(format!(
+                "{} mentioned you in {} on {}, but removed the mention afterwards.",
+                author.mention(),
+                link,
+                guild_name
+            ))
+            .field("Message", truncate(&stored.content, FIELD_LIMIT), false);
+        let sent = match user_id.create_dm_channel(ctx).await {
+            Ok(channel) => channel
+                .send_message(ctx, CreateMessage::new().embed(notice))
+                .await
+                .map(|_| ()),
+            Err(why) => Err(why),
+        };
+        if let Err(why) = sent {
+            debug!("Couldn't notify {} about a ghost ping: {}", user_id, why);
+        }
+    }
+    Ok(())
+}
+
+async fn archive_attachments(data: &Data, message: &serenity::Message) -> DoomResult<()> {
+    for attachment in &message.attachments {
+        let hash = match data
+            .archive
+            .archive(
+                &attachment.url,
+                attachment.content_type.as_deref(),
+                u64::from(attachment.size),
+            )
+            .await
+        {
+            Ok(hash) => hash,
+            Err(why) => {
+                warn!("Couldn't archive attachment {}: {}", attachment.id, why);
+                None
+            }
+        };
+        let stored = Attachment {
+            id: attachment.id.get() as i64,
+            message_id: message.id.get() as i64,
+            filename: attachment.filename.clone(),
+            content_type: attachment.content_type.clone(),
+            size: i64::from(attachment.size),
+            url: attachment.url.clone(),
+            hash,
+            removed_at: None,
+        };
+        data.db
+            .run(move |pool| create_attachment(pool, stored))
+            .await?;
+    }
+    Ok(())
+}
+
+fn fits_field(content: &str) -> bool {
+    content.chars().count() <= FIELD_LIMIT
+}
+
+/// Full text of a message which doesn't fit into an embed field.
+fn text_file(name: String, content: &str) -> CreateAttachment {
+    CreateAttachment::bytes(content.as_bytes().to_vec(), name)
+}
+
+/// Lists the stored attachments of a message and re-uploads the archived ones, as far as
+/// Discord's upload limit and `max_files` permit.
+async fn restore_attachments(
+    data: &Data,
+    attachments: Vec<Attachment>,
+    max_files: usize,
+) -> (Option<String>, Vec<CreateAttachment>) {
+    if attachments.is_empty() {
+        return (None, vec![]);
+    }
+
+    let mut listing = vec![];
+    let mut files = vec![];
+    let mut uploaded = 0;
+    for attachment in attachments {
+        let restored = match &attachment.hash {
+            Some(hash)
+                if uploaded + attachment.size as u64 <= UPLOAD_LIMIT && files.len() < max_files =>
+            {
+                match data.archive.load(hash).await {
+                    Ok(bytes) => {
+                        uploaded += bytes.len() as u64;
+                        files.push(CreateAttachment::bytes(bytes, &attachment.filename));
+                        true
+                    }
+                    Err(why) => {
+                        warn!(
+                            "Couldn't load archived attachment {}: {}",
+                            attachment.id, why
+                        );
+                        false
+                    }
+                }
+            }
+            _ => false,
+        };
+        listing.push(format!(
+            "{} ({} bytes){}",
+            attachment.filename,
+            attachment.size,
+            if restored { "" } else { " - not restored" }
+        ));
+    }
+    (Some(listing.join("\n")), files)
+}
+
+async fn log_content_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    event: &MessageUpdateEvent,
+    user: &User,
+    guild_id: u64,
+    stored: Option<&Message>,
+    content: &str,
+) -> DoomResult<()> {
+    let message_id = event.id.get();
+    let previous_content = stored
+        .as_ref()
+        .map_or("<unknown message>".to_string(), |m| m.content.clone());
+    if previous_content == content {
+        return Ok(());
+    }
+
+    let changes =
+        stored.map(|message| diff::render(&diff::diff(&message.content, content), FIELD_LIMIT));
+    let mut files = vec![];
+    if !fits_field(&previous_content) || !fits_field(content) {
+        files.push(text_file(
+            format!("old-{}.txt", message_id),
+            &previous_content,
+        ));
+        files.push(text_file(format!("new-{}.txt", message_id), content));
+    }
+
+    let edited_at = event
+        .edited_timestamp
+        .unwrap_or_else(Timestamp::now)
+        .naive_utc();
+    let revision = if stored.is_some() {
+        let text = content.to_string();
+        data.db
+            .run(move |pool| add_message_revision(pool, message_id, text, edited_at))
+            .await?
+    } else {
+        data.db
+            .create_message(Message {
+                id: message_id as i64,
+                author: user.id.get() as i64,
+                content: content.to_string(),
+                guild_id: Some(guild_id as i64),
+                channel_id: Some(event.channel_id.get() as i64),
+                created_at: event
+                    .timestamp
+                    .unwrap_or_else(|| event.id.created_at())
+                    .naive_utc(),
+                edited_at: Some(edited_at),
+                reply_to: event
+                    .message_reference
+                    .clone()
+                    .flatten()
+                    .and_then(|r| r.message_id)
+                    .map(|id| id.get() as i64),
+                flags: event.flags.flatten().map_or(0, |f| f.bits() as i64),
+                pinned: event.pinned.unwrap_or(false),
+            })
+            .await?;
+        1
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title("Message Updated")
+        .url(construct_msg_ref(
+            guild_id,
+            event.channel_id.get(),
+            message_id,
+        ))
+        .timestamp(Timestamp::now())
+        .colour(Colour::ORANGE)
+        .field("Author", format!("{} ({})", user.mention(), user.id), true)
+        .field("Revision", revision.to_string(), true)
+        .field("Channel", event.channel_id.mention().to_string(), true);
+    if let Some(message) = stored {
+        embed = embed.field("Posted", format_timestamp(message.created_at), true);
+        if let Some(reply_to) = message.reply_to {
+            embed = embed.field(
+                "Reply To",
+                construct_msg_ref(guild_id, event.channel_id.get(), reply_to as u64),
+                false,
+            );
+        }
+    }
+    if let Some(changes) = changes {
+        embed = embed.field("Changes", changes, false);
+    }
+    embed = embed
+        .field(
+            "Old Message",
+            truncate(&previous_content, FIELD_LIMIT),
+            false,
+        )
+        .field("New Message", truncate(content, FIELD_LIMIT), false)
+        .footer(CreateEmbedFooter::new(&user.name).icon_url(user.face()));
+
+    send_log(
+        ctx,
+        data,
+        Some(GuildId::new(guild_id)),
+        LogCategory::MessageEdits,
+        CreateMessage::new().embed(embed).add_files(files),
+    )
+    .await
+}
+
+/// Logs every change of a message apart from its content, each in an embed of its own.
+async fn log_state_changes(
+    ctx: &serenity::Context,
+    data: &Data,
+    old_if_available: Option<&serenity::Message>,
+    event: &MessageUpdateEvent,
+    user: &User,
+    guild_id: u64,
+    stored: &Message,
+) -> DoomResult<()> {
+    let message_id = event.id.get();
+    let attachments: Vec<Attachment> = data
+        .db
+        .run(move |pool| get_attachments_by_message(pool, message_id))
+        .await?
+        .into_iter()
+        .filter(|attachment| attachment.removed_at.is_none())
+        .collect();
+    let before = MessageState {
+        attachments: Some(attachments.iter().map(|a| a.id as u64).collect()),
+        flags: Some(MessageFlags::from_bits_truncate(stored.flags as u64)),
+        pinned: Some(stored.pinned),
+        components: old_if_available.and_then(|m| serde_json::to_value(&m.components).ok()),
+    };
+    let after = MessageState::from_update(event);
+
+    let changes = detect_changes(&before, &after);
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let base_embed = |title: &str, colour: Colour| {
+        CreateEmbed::new()
+            .title(title)
+            .url(construct_msg_ref(
+                guild_id,
+                event.channel_id.get(),
+                message_id,
+            ))
+            .timestamp(Timestamp::now())
+            .colour(colour)
+            .field("Author", format!("{} ({})", user.mention(), user.id), true)
+            .field("Channel", event.channel_id.mention().to_string(), true)
+            .footer(CreateEmbedFooter::new(&user.name).icon_url(user.face()))
+    };
+
+    for change in changes {
+        let mut message = CreateMessage::new();
+        let embed = match change {
+            MessageChange::AttachmentsRemoved(ids) => {
+                let removed: Vec<Attachment> = attachments
+                    .iter()
+                    .filter(|a| ids.contains(&(a.id as u64)))
+                    .cloned()
+                    .collect();
+                let removed_ids: Vec<i64> = removed.iter().map(|a| a.id).collect();
+                let removed_at = Timestamp::now().naive_utc();
+                data.db
+                    .run(move |pool| mark_attachments_removed(pool, &removed_ids, removed_at))
+                    .await?;
+
+                let (listing, files) = restore_attachments(data, removed, MAX_FILES).await;
+                message = message.add_files(files);
+                base_embed("Attachment Removed", Colour::DARK_ORANGE).field(
+                    "Removed Attachments",
+                    listing.unwrap_or_default(),
+                    false,
+                )
+            }
+            MessageChange::EmbedsSuppressed => base_embed("Embeds Suppressed", Colour::LIGHT_GREY),
+            MessageChange::EmbedsRestored => base_embed("Embeds Restored", Colour::LIGHT_GREY),
+            MessageChange::FlagsChanged { added, removed } => {
+                base_embed("Message Flags Changed", Colour::BLUE)
+                    .field("Added", flag_names(added), false)
+                    .field("Removed", flag_names(removed), false)
+            }
+            // Logged from the pins update instead, which tells who (un)pinned the message.
+            MessageChange::Pinned | MessageChange::Unpinned => continue,
+            MessageChange::ComponentsChanged => {
+                base_embed("Message Components Changed", Colour::BLUE)
+            }
+        };
+        send_log(
+            ctx,
+            data,
+            Some(GuildId::new(guild_id)),
+            LogCategory::MessageEdits,
+            message.embed(embed),
+        )
+        .await?;
+    }
+
+    let flags = after.flags.map_or(stored.flags, |f| f.bits() as i64);
+    let pinned = after.pinned.unwrap_or(stored.pinned);
+    data.db
+        .run(move |pool| update_message_state(pool, message_id, flags, pinned))
+        .await
+}
+
+/// Renders stored messages as plain-text and HTML transcripts, named after the channel.
+async fn transcript_files(
+    ctx: &serenity::Context,
+    title: &str,
+    channel_id: ChannelId,
+    messages: &[Message],
+) -> [CreateAttachment; 2] {
+    let authors: BTreeSet<i64> = messages.iter().map(|message| message.author).collect();
+    let mut names = HashMap::new();
+    for author in authors {
+        if let Ok(user) = UserId::new(author as u64).to_user(ctx).await {
+            names.insert(author, user.name);
+        }
+    }
+    let file_name = format!("transcript-{}-{}", channel_id, Timestamp::now().timestamp());
+    [
+        CreateAttachment::bytes(
+            transcript::plain_text(title, messages, &names),
+            format!("{}.txt", file_name),
+        ),
+        CreateAttachment::bytes(
+            transcript::html(title, messages, &names),
+            format!("{}.html", file_name),
+        ),
+    ]
+}
+
+/// Logs a bulk deletion (i.e. a purge) as one summary with a transcript of the stored messages.
+async fn log_bulk_deletion(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    message_ids: &[MessageId],
+) -> DoomResult<()> {
+    let ids: Vec<u64> = message_ids.iter().map(|id| id.get()).collect();
+    let mut messages = {
+        let ids = ids.clone();
+        data.db
+            .run(move |pool| get_messages_by_ids(pool, &ids))
+            .await?
+    };
+    if let Some(guild_id) = guild_id {
+        messages.retain(|message| {
+            let author = UserId::new(message.author as u64);
+            !is_ignored(ctx, data, guild_id, channel_id, Some(author), None)
+        });
+    }
+    debug!(
+        "{} messages bulk deleted in {:?}, {} of them stored.",
+        ids.len(),
+        channel_id,
+        messages.len()
+    );
+
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for message in &messages {
+        *counts.entry(message.author).or_default() += 1;
+    }
+    let mut counts: Vec<(i64, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+
+    let authors: Vec<String> = counts
+        .iter()
+        .map(|(author, count)| {
+            let author = UserId::new(*author as u64);
+            format!("{} ({}): {}", author.mention(), author, count)
+        })
+        .collect();
+    let mut authors = join_limited(&authors, "\n", FIELD_LIMIT);
+    if authors.is_empty() {
+        authors = "-".to_string();
+    }
+
+    let embed = CreateEmbed::new()
+        .title("Messages Bulk Deleted")
+        .timestamp(Timestamp::now())
+        .colour(Colour::DARK_RED)
+        .field("Channel", channel_id.mention().to_string(), true)
+        .field("Deleted", ids.len().to_string(), true)
+        .field("Stored", messages.len().to_string(), true)
+        .field("Authors", authors, false);
+
+    let mut message = CreateMessage::new().embed(embed);
+    if !messages.is_empty() {
+        let title = format!(
+            "Bulk deletion in #{} ({}) at {}",
+            channel_id
+                .name(ctx)
+                .await
+                .unwrap_or_else(|_| "unknown-channel".to_string()),
+            channel_id,
+            Timestamp::now().format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        message = message.add_files(transcript_files(ctx, &title, channel_id, &messages).await);
+    }
+
+    send_log(ctx, data, guild_id, LogCategory::MessageDeletes, message).await
+}
+
+/// Logs failures of individual events, so they don't take the bot down with them.
 pub async fn event_handler(
     ctx: &serenity::Context,
     event: &FullEvent,
     framework: poise::FrameworkContext<'_, Data, Error>,
     data: &Data,
 ) -> Result<(), Error> {
+    if let Err(why) = handle_event(ctx, event, framework, data).await {
+        error!("Error while handling {}: {}", event.snake_case_name(), why);
+    }
+    Ok(())
+}
+
+async fn handle_event(
+    ctx: &serenity::Context,
+    event: &FullEvent,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    data: &Data,
+) -> DoomResult<()> {
     match event {
         FullEvent::Ready { data_about_bot, .. } => {
             info!("Logged in as {}", data_about_bot.user.tag());
             if data.environment != "develop" {
-                let msg = CreateMessage::new().content("Logging Channel Found :white_check_mark:");
-                data.log_channel
-                    .send_message(&ctx.http, msg)
-                    .await
-                    .expect("Could not send message to logging channel.");
+                for channel in data.config.log_channels(LogCategory::BotStatus) {
+                    let msg =
+                        CreateMessage::new().content("Logging Channel Found :white_check_mark:");
+                    if let Err(why) = channel.send_message(&ctx.http, msg).await {
+                        warn!("Couldn't greet log channel {}: {}", channel, why);
+                    }
+                }
             }
         }
         FullEvent::Message { new_message, .. } => {
             if new_message.author.id == framework.bot_id {
                 return Ok(());
             }
+            if let Some(guild_id) = new_message.guild_id {
+                let roles = new_message.member.as_ref().map(|m| m.roles.as_slice());
+                let author = Some(new_message.author.id);
+                if is_ignored(ctx, data, guild_id, new_message.channel_id, author, roles) {
+                    return Ok(());
+                }
+            }
 
-            create_message(
-                &data.pool,
-                new_message.id.get(),
-                new_message.author.id.get(),
-                new_message.content.clone(),
-            );
+            data.db.create_message(Message::from(new_message)).await?;
+            archive_attachments(data, new_message).await?;
 
-            let entries = get_message_count(&data.pool);
+            let entries = data.db.run(get_message_count).await?;
             if entries % 1000 == 0 {
                 info!("Database contains {} messages", entries);
                 let msg = CreateMessage::new()
                     .content(format!("Database now contains {} messages.", entries));
-                data.log_channel
-                    .send_message(&ctx.http, msg)
-                    .await
-                    .expect("Could not send message to logging channel.");
+                send_log(ctx, data, new_message.guild_id, LogCategory::BotStatus, msg).await?;
             }
         }
-        FullEvent::MessageUpdate { event, .. } => {
+        FullEvent::MessageUpdate {
+            old_if_available,
+            event,
+            ..
+        } => {
             debug!("Message {:?} updated to: {:?}", event.id, event.content);
 
             let message_id = event.id.get();
             let user_id = match &event.author {
                 Some(user) => user.id.get(),
-                None => get_author_from_message(&data.pool, message_id),
+                None => {
+                    data.db
+                        .run(move |pool| get_author_from_message(pool, message_id))
+                        .await?
+                }
             };
             if user_id == UNKNOWN_USER {
                 return Ok(());
             }
 
-            let user = UserId::new(user_id)
-                .to_user(&ctx.http)
-                .await
-                .expect("User should exist.");
-            if user.bot {
+            let user = UserId::new(user_id).to_user(&ctx.http).await?;
+            if user.bot || user.id == framework.bot_id {
                 return Ok(());
             }
 
@@ -83,72 +897,79 @@ pub async fn event_handler(
             if guild_id == 0 {
                 return Ok(());
             }
+            let guild = GuildId::new(guild_id);
+            if is_ignored(ctx, data, guild, event.channel_id, Some(user.id), None) {
+                return Ok(());
+            }
 
-            match &event.content {
-                Some(content) => {
-                    let mut previous_content = get_message_content_by_id(&data.pool, message_id);
-                    if previous_content.eq(content) {
-                        warn!("TODO: Implement non-content message updates (i.e. Embeds)");
-                        return Ok(());
-                    }
-
-                    previous_content.truncate(1024);
-
-                    let mut current_content = content.clone();
-                    current_content.truncate(1024);
-
-                    if framework.bot_id == user_id {
-                        return Ok(());
-                    }
-
-                    let embed = CreateEmbed::new()
-                        .title("Message Updated")
-                        .url(construct_msg_ref(
-                            guild_id,
-                            event.channel_id.get(),
-                            message_id,
-                        ))
-                        .timestamp(Timestamp::now())
-                        .colour(Colour::ORANGE)
-                        .field("Author", format!("{} ({})", user.mention(), user_id), false)
-                        .field("Old Message", previous_content, false)
-                        .field("New Message", current_content, false)
-                        .footer(CreateEmbedFooter::new(&user.name).icon_url(user.face()));
-
-                    data.log_channel
-                        .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                        .await
-                        .expect("Unable to send message");
-
-                    if exists_message(&data.pool, message_id) {
-                        update_message_content(&data.pool, message_id, content.clone());
-                    } else {
-                        create_message(&data.pool, message_id, user.id.get(), content.clone());
-                    }
-                }
-                None => {
-                    warn!("Message Update occurred not in message content")
+            let stored = data
+                .db
+                .run(move |pool| get_message_by_id(pool, message_id))
+                .await?;
+            if let Some(content) = &event.content {
+                log_content_update(ctx, data, event, &user, guild_id, stored.as_ref(), content)
+                    .await?;
+                if let Some(stored) = &stored {
+                    check_ghost_ping(ctx, data, guild, stored, Some(content)).await?;
                 }
             }
+
+            // Without a stored message there is nothing to compare the update against.
+            if let Some(stored) = &stored {
+                log_state_changes(
+                    ctx,
+                    data,
+                    old_if_available.as_ref(),
+                    event,
+                    &user,
+                    guild_id,
+                    stored,
+                )
+                .await?;
+            }
         }
         FullEvent::MessageDelete {
-            deleted_message_id, ..
+            channel_id,
+            deleted_message_id,
+            guild_id,
         } => {
-            let (user_id, content) =
-                get_message_content_and_author_by_id(&data.pool, deleted_message_id.get());
+            let message_id = deleted_message_id.get();
+            let stored = data
+                .db
+                .run(move |pool| get_message_by_id(pool, message_id))
+                .await?;
+            let user_id = stored.as_ref().map_or(UNKNOWN_USER, |m| m.author as u64);
 
             if user_id == framework.bot_id.get() {
                 return Ok(());
             }
 
+            // Discord resolves links to deleted messages to the surrounding conversation.
+            let guild_id = guild_id
+                .map(|id| id.get())
+                .or_else(|| stored.as_ref().and_then(|m| m.guild_id.map(|id| id as u64)));
+            if let Some(guild_id) = guild_id {
+                let author = (user_id != UNKNOWN_USER).then(|| UserId::new(user_id));
+                if is_ignored(ctx, data, GuildId::new(guild_id), *channel_id, author, None) {
+                    return Ok(());
+                }
+            }
+
             debug!("Message {:?} deleted.", deleted_message_id);
 
             let mut embed = CreateEmbed::new()
                 .title("Message Deleted")
                 .timestamp(Timestamp::now())
                 .colour(Colour::DARK_RED);
+            if let Some(guild_id) = guild_id {
+                embed = embed.url(construct_msg_ref(
+                    guild_id,
+                    channel_id.get(),
+                    deleted_message_id.get(),
+                ));
+            }
 
-            if user_id != 0 {
+            if user_id != UNKNOWN_USER {
                 let id = UserId::new(user_id);
                 match id.to_user(&ctx.http).await {
                     Ok(user) => {
@@ -162,12 +983,255 @@ pub async fn event_handler(
                 embed = embed.field("Author", format!("{} ({})", id.mention(), user_id), false);
             }
 
-            embed = embed.field("Deleted Message", content, false);
+            embed = embed.field("Channel", channel_id.mention().to_string(), true);
 
-            data.log_channel
-                .send_message(&ctx.http, CreateMessage::new().embed(embed))
-                .await
-                .expect("Unable to send message");
+            let content = match &stored {
+                Some(message) => {
+                    embed = embed.field("Posted", format_timestamp(message.created_at), true);
+                    if let Some(edited_at) = message.edited_at {
+                        embed = embed.field("Last Edited", format_timestamp(edited_at), true);
+                    }
+                    if let (Some(reply_to), Some(guild_id)) = (message.reply_to, guild_id) {
+                        embed = embed.field(
+                            "Reply To",
+                            construct_msg_ref(guild_id, channel_id.get(), reply_to as u64),
+                            false,
+                        );
+                    }
+                    let flags = MessageFlags::from_bits_truncate(message.flags as u64);
+                    if !flags.is_empty() {
+                        embed = embed.field("Flags", flag_names(flags), false);
+                    }
+                    message.content.clone()
+                }
+                None => "<unknown_message>".to_string(),
+            };
+
+            embed = embed.field("Deleted Message", truncate(&content, FIELD_LIMIT), false);
+            let full_text = (!fits_field(&content))
+                .then(|| text_file(format!("deleted-{}.txt", message_id), &content));
+
+            let (listing, mut files) = restore_attachments(
+                data,
+                data.db
+                    .run(move |pool| get_attachments_by_message(pool, message_id))
+                    .await?,
+                MAX_FILES - usize::from(full_text.is_some()),
+            )
+            .await;
+            files.extend(full_text);
+            if let Some(listing) = listing {
+                embed = embed.field("Attachments", listing, false);
+            }
+
+            let sent = post_log(
+                ctx,
+                data,
+                guild_id.map(GuildId::new),
+                LogCategory::MessageDeletes,
+                CreateMessage::new().embed(embed.clone()).add_files(files),
+            )
+            .await?;
+
+            let Some(guild_id) = guild_id.map(GuildId::new) else {
+                return Ok(());
+            };
+            if user_id == UNKNOWN_USER || !can_view_audit_log(ctx, guild_id) {
+                if let Some(stored) = &stored {
+                    check_ghost_ping(ctx, data, guild_id, stored, None).await?;
+                }
+                return Ok(());
+            }
+
+            // The audit log lags behind, so the deleter is added to the sent log once it's in.
+            let (ctx, data, channel_id) = (ctx.clone(), data.clone(), *channel_id);
+            tokio::spawn(async move {
+                let author = UserId::new(user_id);
+                let deleter = find_deleter(&ctx, &data, guild_id, channel_id, author).await;
+                if let (Some(sent), Some(entry)) = (sent, &deleter) {
+                    attribute(&ctx, sent, with_deleter(embed, entry)).await;
+                }
+                // A moderator removing a message doesn't make its mentions a ghost ping.
+                let Some(stored) = stored.filter(|_| deleted_by_author(author, deleter.as_ref()))
+                else {
+                    return;
+                };
+                if let Err(why) = check_ghost_ping(&ctx, &data, guild_id, &stored, None).await {
+                    warn!(
+                        "Couldn't check message {} for ghost pings: {}",
+                        stored.id, why
+                    );
+                }
+            });
+        }
+        FullEvent::GuildMemberAddition { new_member } => {
+            member::log_member_addition(ctx, data, new_member).await?;
+        }
+        FullEvent::ChannelCreate { channel } => {
+            pins::seed_new_channel(data, channel).await?;
+            channel::log_channel_create(ctx, data, channel).await?;
+        }
+        FullEvent::CategoryCreate { category } => {
+            channel::log_channel_create(ctx, data, category).await?;
+        }
+        FullEvent::ChannelUpdate { old, new } => {
+            channel::log_channel_update(ctx, data, old.as_ref(), new).await?;
+        }
+        FullEvent::ChannelDelete { channel, .. } => {
+            channel::log_channel_delete(ctx, data, channel).await?;
+        }
+        FullEvent::CategoryDelete { category } => {
+            channel::log_channel_delete(ctx, data, category).await?;
+        }
+        FullEvent::GuildRoleCreate { new } => {
+            role::log_role_create(ctx, data, new).await?;
+        }
+        FullEvent::GuildRoleUpdate {
+            old_data_if_available,
+            new,
+        } => {
+            role::log_role_update(ctx, data, old_data_if_available.as_ref(), new).await?;
+        }
+        FullEvent::GuildRoleDelete {
+            guild_id,
+            removed_role_id,
+            removed_role_data_if_available,
+        } => {
+            role::log_role_delete(
+                ctx,
+                data,
+                *guild_id,
+                *removed_role_id,
+                removed_role_data_if_available.as_ref(),
+            )
+            .await?;
+        }
+        FullEvent::GuildCreate { guild, .. } => {
+            data.assets.seed(guild);
+            invite::seed(ctx, data, guild.id).await;
+            automod::seed(ctx, data, guild.id).await;
+            pins::seed(ctx, data, guild);
+        }
+        FullEvent::AutoModRuleCreate { rule } => {
+            automod::log_rule_create(ctx, data, rule).await?;
+        }
+        FullEvent::AutoModRuleUpdate { rule } => {
+            automod::log_rule_update(ctx, data, rule).await?;
+        }
+        FullEvent::AutoModRuleDelete { rule } => {
+            automod::log_rule_delete(ctx, data, rule).await?;
+        }
+        FullEvent::AutoModActionExecution { execution } => {
+            automod::log_action_execution(ctx, data, execution).await?;
+        }
+        FullEvent::ChannelPinsUpdate { pin } => {
+            pins::log_pins_update(ctx, data, pin).await?;
+        }
+        FullEvent::InviteCreate { data: created } => {
+            if let Some(guild_id) = created.guild_id {
+                data.invites
+                    .insert(guild_id, created.code.clone(), created.into());
+            }
+        }
+        FullEvent::InviteDelete { data: deleted } => {
+            if let Some(guild_id) = deleted.guild_id {
+                let now = Timestamp::now().naive_utc();
+                data.invites.remove(guild_id, &deleted.code, now);
+            }
+        }
+        FullEvent::GuildUpdate {
+            old_data_if_available,
+            new_data,
+        } => {
+            server::log_guild_update(ctx, data, old_data_if_available.as_ref(), new_data).await?;
+        }
+        FullEvent::GuildEmojisUpdate {
+            guild_id,
+            current_state,
+        } => {
+            let emojis = server::emoji_assets(current_state);
+            let before = data.assets.replace_emojis(*guild_id, emojis.clone());
+            server::log_asset_changes(ctx, data, *guild_id, AssetKind::Emoji, before, &emojis)
+                .await?;
+        }
+        FullEvent::GuildStickersUpdate {
+            guild_id,
+            current_state,
+        } => {
+            let stickers = server::sticker_assets(current_state);
+            let before = data.assets.replace_stickers(*guild_id, stickers.clone());
+            server::log_asset_changes(ctx, data, *guild_id, AssetKind::Sticker, before, &stickers)
+                .await?;
+        }
+        FullEvent::ThreadCreate { thread } => {
+            thread::log_thread_create(ctx, data, thread).await?;
+        }
+        FullEvent::ThreadUpdate { old, new } => {
+            thread::log_thread_update(ctx, data, old.as_ref(), new).await?;
+        }
+        FullEvent::ThreadDelete {
+            thread,
+            full_thread_data,
+        } => {
+            thread::log_thread_delete(ctx, data, thread, full_thread_data.as_ref()).await?;
+        }
+        FullEvent::VoiceStateUpdate { old, new } => {
+            voice::log_voice_update(ctx, data, old.as_ref(), new).await?;
+        }
+        FullEvent::GuildMemberUpdate {
+            old_if_available,
+            event,
+            ..
+        } => {
+            member::log_member_update(ctx, data, old_if_available.as_ref(), event).await?;
+        }
+        FullEvent::GuildMemberRemoval {
+            guild_id,
+            user,
+            member_data_if_available,
+        } => {
+            moderation::check_kick(ctx, data, *guild_id, user);
+            member::log_member_removal(
+                ctx,
+                data,
+                *guild_id,
+                user,
+                member_data_if_available.as_ref(),
+            )
+            .await?;
+        }
+        FullEvent::GuildBanAddition {
+            guild_id,
+            banned_user,
+        } => {
+            moderation::log_ban_change(ctx, data, *guild_id, banned_user, ModeratorAction::Ban)
+                .await?;
+        }
+        FullEvent::GuildBanRemoval {
+            guild_id,
+            unbanned_user,
+        } => {
+            moderation::log_ban_change(ctx, data, *guild_id, unbanned_user, ModeratorAction::Unban)
+                .await?;
+        }
+        FullEvent::MessageDeleteBulk {
+            channel_id,
+            multiple_deleted_messages_ids,
+            guild_id,
+        } => {
+            if let Some(guild_id) = guild_id {
+                if is_ignored(ctx, data, *guild_id, *channel_id, None, Some(&[])) {
+                    return Ok(());
+                }
+            }
+            log_bulk_deletion(
+                ctx,
+                data,
+                *guild_id,
+                *channel_id,
+                multiple_deleted_messages_ids,
+            )
+            .await?;
         }
         _ => {}
     }
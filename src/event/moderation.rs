@@ -0,0 +1,211 @@
+use super::{
+    can_view_audit_log, find_actor, post_attributed_log, send_log, with_actor, Attribution,
+};
+use crate::audit::AuditEntry;
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::persistence::models::NewModerationAction;
+use crate::persistence::{record_moderation_action, set_moderation_actor};
+use crate::Data;
+use log::warn;
+use poise::serenity_prelude::audit_log::{Action, MemberAction};
+use poise::serenity_prelude::{
+    self as serenity, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId, Mentionable,
+    Timestamp, User, UserId,
+};
+
+/// The kinds of actions in the moderation history of a user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ModerationKind {
+    Ban,
+    Unban,
+    Kick,
+    /// Content blocked or flagged by one of Discord's AutoMod rules.
+    AutoMod,
+}
+
+impl ModerationKind {
+    pub const ALL: [ModerationKind; 4] = [
+        ModerationKind::Ban,
+        ModerationKind::Unban,
+        ModerationKind::Kick,
+        ModerationKind::AutoMod,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            ModerationKind::Ban => "ban",
+            ModerationKind::Unban => "unban",
+            ModerationKind::Kick => "kick",
+            ModerationKind::AutoMod => "automod",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.key() == key)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ModerationKind::Ban => "Ban",
+            ModerationKind::Unban => "Unban",
+            ModerationKind::Kick => "Kick",
+            ModerationKind::AutoMod => "AutoMod",
+        }
+    }
+}
+
+/// What a moderator did to a user, as found in the audit log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeratorAction {
+    Ban,
+    Unban,
+    Kick,
+}
+
+impl ModeratorAction {
+    fn kind(self) -> ModerationKind {
+        match self {
+            ModeratorAction::Ban => ModerationKind::Ban,
+            ModeratorAction::Unban => ModerationKind::Unban,
+            ModeratorAction::Kick => ModerationKind::Kick,
+        }
+    }
+
+    fn action(self) -> Action {
+        match self {
+            ModeratorAction::Ban => Action::Member(MemberAction::BanAdd),
+            ModeratorAction::Unban => Action::Member(MemberAction::BanRemove),
+            ModeratorAction::Kick => Action::Member(MemberAction::Kick),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ModeratorAction::Ban => "Banned by",
+            ModeratorAction::Unban => "Unbanned by",
+            ModeratorAction::Kick => "Kicked by",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            ModeratorAction::Ban => "Member Banned",
+            ModeratorAction::Unban => "User Unbanned",
+            ModeratorAction::Kick => "Member Kicked",
+        }
+    }
+
+    fn colour(self) -> Colour {
+        match self {
+            ModeratorAction::Ban => Colour::DARK_RED,
+            ModeratorAction::Unban => Colour::DARK_GREEN,
+            ModeratorAction::Kick => Colour::RED,
+        }
+    }
+}
+
+fn action_embed(kind: ModeratorAction, user: &User) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(kind.title())
+        .timestamp(Timestamp::now())
+        .colour(kind.colour())
+        .thumbnail(user.face())
+        .field("User", format!("{} ({})", user.mention(), user.id), false)
+        .footer(CreateEmbedFooter::new(&user.name).icon_url(user.face()))
+}
+
+async fn record_action(
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    kind: ModeratorAction,
+    entry: Option<&AuditEntry>,
+) -> DoomResult<i32> {
+    let action = NewModerationAction {
+        guild_id: guild_id.get() as i64,
+        user_id: user_id.get() as i64,
+        moderator_id: entry.map(|entry| entry.user_id.get() as i64),
+        kind: kind.kind().key().to_string(),
+        reason: entry.and_then(|entry| entry.reason.clone()),
+        created_at: Timestamp::now().naive_utc(),
+    };
+    data.db
+        .run(move |pool| record_moderation_action(pool, action))
+        .await
+}
+
+/// Logs and records a ban or unban right away, adding the moderator and reason to both once the
+/// audit log has them.
+pub(super) async fn log_ban_change(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user: &User,
+    kind: ModeratorAction,
+) -> DoomResult<()> {
+    let recorded = record_action(data, guild_id, user.id, kind, None).await?;
+    let lookup = post_attributed_log(
+        ctx,
+        data,
+        guild_id,
+        LogCategory::Moderation,
+        CreateMessage::new(),
+        action_embed(kind, user),
+        Attribution::new(kind.label(), &[kind.action()], user.id.get()),
+    )
+    .await?;
+    let db = data.db.clone();
+    tokio::spawn(async move {
+        let Ok(Some(entry)) = lookup.await else {
+            return;
+        };
+        let moderator = entry.user_id.get();
+        let reason = entry.reason;
+        if let Err(why) = db
+            .run(move |pool| set_moderation_actor(pool, recorded, moderator, reason))
+            .await
+        {
+            warn!("Couldn't add the moderator to action {}: {}", recorded, why);
+        }
+    });
+    Ok(())
+}
+
+/// Records a kick if the audit log shows the removal of a member was one. The lookup runs on its
+/// own, so it neither waits for nor depends on the removal being logged.
+pub(super) fn check_kick(ctx: &serenity::Context, data: &Data, guild_id: GuildId, user: &User) {
+    if !can_view_audit_log(ctx, guild_id) {
+        return;
+    }
+    let (ctx, data, user) = (ctx.clone(), data.clone(), user.clone());
+    tokio::spawn(async move {
+        let kind = ModeratorAction::Kick;
+        let Some(entry) = find_actor(&ctx, guild_id, &[kind.action()], user.id.get()).await else {
+            return;
+        };
+        if let Err(why) = log_kick(&ctx, &data, guild_id, &user, entry).await {
+            warn!("Couldn't record the kick of {}: {}", user.id, why);
+        }
+    });
+}
+
+async fn log_kick(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user: &User,
+    entry: AuditEntry,
+) -> DoomResult<()> {
+    let kind = ModeratorAction::Kick;
+    record_action(data, guild_id, user.id, kind, Some(&entry)).await?;
+    let embed = with_actor(action_embed(kind, user), kind.label(), Some(entry));
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::Moderation,
+        CreateMessage::new().embed(embed),
+    )
+    .await
+}
@@ -0,0 +1,167 @@
+use super::{construct_msg_ref, is_ignored, send_attributed_log, send_log, Attribution};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::persistence::{get_channel_pins, get_message_by_id, save_channel_pins};
+use crate::util::discord::{truncate, FIELD_LIMIT};
+use crate::Data;
+use log::{debug, warn};
+use poise::serenity_prelude::audit_log::{Action, MessageAction};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, ChannelPinsUpdateEvent, Colour, CreateEmbed, CreateMessage, Guild,
+    GuildChannel, Mentionable, Timestamp, UserId,
+};
+use std::collections::BTreeSet;
+
+#[derive(Debug, PartialEq)]
+pub enum PinChange {
+    Pinned(u64),
+    Unpinned(u64),
+}
+
+pub fn detect_pin_changes(before: &BTreeSet<u64>, after: &BTreeSet<u64>) -> Vec<PinChange> {
+    after
+        .difference(before)
+        .map(|id| PinChange::Pinned(*id))
+        .chain(before.difference(after).map(|id| PinChange::Unpinned(*id)))
+        .collect()
+}
+
+async fn store_pins(data: &Data, channel_id: ChannelId, pins: BTreeSet<u64>) -> DoomResult<()> {
+    data.db
+        .run(move |pool| save_channel_pins(pool, channel_id.get(), &pins))
+        .await
+}
+
+/// Stores the pins of the channels and active threads of a guild, so their first change has
+/// something to compare to. Runs on its own, as it takes a request per channel.
+pub(super) fn seed(ctx: &serenity::Context, data: &Data, guild: &Guild) {
+    let channels: Vec<ChannelId> = guild
+        .channels
+        .values()
+        .filter(|channel| channel.is_text_based())
+        .chain(&guild.threads)
+        .map(|channel| channel.id)
+        .collect();
+    let (ctx, data) = (ctx.clone(), data.clone());
+    tokio::spawn(async move {
+        for channel_id in channels {
+            let pins = match channel_id.pins(&ctx.http).await {
+                Ok(pins) => pins.iter().map(|message| message.id.get()).collect(),
+                Err(why) => {
+                    debug!("Couldn't fetch the pins of {}: {}", channel_id, why);
+                    continue;
+                }
+            };
+            if let Err(why) = store_pins(&data, channel_id, pins).await {
+                warn!("Couldn't store the pins of {}: {}", channel_id, why);
+            }
+        }
+    });
+}
+
+/// New channels start out without pins, which saves fetching them.
+pub(super) async fn seed_new_channel(data: &Data, channel: &GuildChannel) -> DoomResult<()> {
+    if !channel.is_text_based() {
+        return Ok(());
+    }
+    store_pins(data, channel.id, BTreeSet::new()).await
+}
+
+/// Logs which messages were pinned or unpinned, by comparing the pins with the stored ones.
+/// Channels which weren't seeded only store their pins on the first change, as there is nothing
+/// to compare to yet.
+pub(super) async fn log_pins_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    event: &ChannelPinsUpdateEvent,
+) -> DoomResult<()> {
+    let Some(guild_id) = event.guild_id else {
+        return Ok(());
+    };
+    let channel_id = event.channel_id;
+    let pins = channel_id.pins(&ctx.http).await?;
+    let current: BTreeSet<u64> = pins.iter().map(|message| message.id.get()).collect();
+    let stored = data
+        .db
+        .run(move |pool| get_channel_pins(pool, channel_id.get()))
+        .await?;
+    store_pins(data, channel_id, current.clone()).await?;
+    let Some(stored) = stored else {
+        return Ok(());
+    };
+
+    for change in detect_pin_changes(&stored, &current) {
+        let (title, label, action, message_id) = match change {
+            PinChange::Pinned(id) => ("Message Pinned", "Pinned by", MessageAction::Pin, id),
+            PinChange::Unpinned(id) => {
+                ("Message Unpinned", "Unpinned by", MessageAction::Unpin, id)
+            }
+        };
+        let message = data
+            .db
+            .run(move |pool| get_message_by_id(pool, message_id))
+            .await?;
+        // Pinned messages are at hand even if they weren't stored.
+        let (author, content) = match (&message, pins.iter().find(|m| m.id == message_id)) {
+            (Some(message), _) => (
+                Some(UserId::new(message.author as u64)),
+                Some(&message.content),
+            ),
+            (None, Some(pin)) => (Some(pin.author.id), Some(&pin.content)),
+            (None, None) => (None, None),
+        };
+        if is_ignored(ctx, data, guild_id, channel_id, author, None) {
+            continue;
+        }
+
+        let mut embed = CreateEmbed::new()
+            .title(title)
+            .url(construct_msg_ref(
+                guild_id.get(),
+                channel_id.get(),
+                message_id,
+            ))
+            .timestamp(Timestamp::now())
+            .colour(Colour::GOLD);
+        if let Some(author) = author {
+            embed = embed.field("Author", format!("{} ({})", author.mention(), author), true);
+        }
+        embed = embed
+            .field("Channel", channel_id.mention().to_string(), true)
+            .field(
+                "Content",
+                match content {
+                    Some(content) if content.is_empty() => "*no text*".to_string(),
+                    Some(content) => truncate(content, FIELD_LIMIT),
+                    None => "*not stored*".to_string(),
+                },
+                false,
+            );
+        // Pins are logged with the author as their target.
+        match author {
+            Some(author) => {
+                send_attributed_log(
+                    ctx,
+                    data,
+                    guild_id,
+                    LogCategory::MessageEdits,
+                    CreateMessage::new(),
+                    embed,
+                    Attribution::new(label, &[Action::Message(action)], author.get()),
+                )
+                .await?
+            }
+            None => {
+                send_log(
+                    ctx,
+                    data,
+                    Some(guild_id),
+                    LogCategory::MessageEdits,
+                    CreateMessage::new().embed(embed),
+                )
+                .await?
+            }
+        }
+    }
+    Ok(())
+}
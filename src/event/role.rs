@@ -0,0 +1,304 @@
+use super::{send_attributed_log, skip_uncached_update, Attribution};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::util::discord::{escape_markdown, permission_name, truncate, FIELD_LIMIT};
+use crate::Data;
+use poise::serenity_prelude::audit_log::{Action, RoleAction};
+use poise::serenity_prelude::{
+    self as serenity, Colour, CreateEmbed, CreateMessage, GuildId, Mentionable, Permissions, Role,
+    RoleId, Timestamp,
+};
+
+/// Permissions which let a role take over or wreck the server, highlighted wherever they show up.
+pub const DANGEROUS_PERMISSIONS: Permissions = Permissions::ADMINISTRATOR
+    .union(Permissions::MANAGE_GUILD)
+    .union(Permissions::MANAGE_ROLES)
+    .union(Permissions::MANAGE_CHANNELS)
+    .union(Permissions::MANAGE_WEBHOOKS)
+    .union(Permissions::BAN_MEMBERS)
+    .union(Permissions::KICK_MEMBERS)
+    .union(Permissions::MODERATE_MEMBERS)
+    .union(Permissions::MANAGE_MESSAGES)
+    .union(Permissions::MENTION_EVERYONE);
+
+/// The parts of a role which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RoleState {
+    pub name: String,
+    pub colour: u32,
+    pub hoist: bool,
+    pub mentionable: bool,
+    pub position: u16,
+    pub permissions: Permissions,
+}
+
+impl RoleState {
+    pub fn from_role(role: &Role) -> Self {
+        RoleState {
+            name: role.name.clone(),
+            colour: role.colour.0,
+            hoist: role.hoist,
+            mentionable: role.mentionable,
+            position: role.position,
+            permissions: role.permissions,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RoleChange {
+    Name {
+        before: String,
+        after: String,
+    },
+    Colour {
+        before: u32,
+        after: u32,
+    },
+    Hoist(bool),
+    Mentionable(bool),
+    Position {
+        before: u16,
+        after: u16,
+    },
+    Permissions {
+        gained: Permissions,
+        lost: Permissions,
+    },
+}
+
+impl RoleChange {
+    /// Moving a role shifts every role in between, each with an update of its own, so changes
+    /// of the position alone aren't logged.
+    pub fn is_position(&self) -> bool {
+        matches!(self, RoleChange::Position { .. })
+    }
+}
+
+pub fn detect_role_changes(before: &RoleState, after: &RoleState) -> Vec<RoleChange> {
+    let mut changes = vec![];
+
+    if before.name != after.name {
+        changes.push(RoleChange::Name {
+            before: before.name.clone(),
+            after: after.name.clone(),
+        });
+    }
+    if before.colour != after.colour {
+        changes.push(RoleChange::Colour {
+            before: before.colour,
+            after: after.colour,
+        });
+    }
+    if before.hoist != after.hoist {
+        changes.push(RoleChange::Hoist(after.hoist));
+    }
+    if before.mentionable != after.mentionable {
+        changes.push(RoleChange::Mentionable(after.mentionable));
+    }
+    if before.position != after.position {
+        changes.push(RoleChange::Position {
+            before: before.position,
+            after: after.position,
+        });
+    }
+    let gained = after.permissions.difference(before.permissions);
+    let lost = before.permissions.difference(after.permissions);
+    if !gained.is_empty() || !lost.is_empty() {
+        changes.push(RoleChange::Permissions { gained, lost });
+    }
+
+    changes
+}
+
+/// Lists permissions as a diff, marking dangerous ones.
+pub fn describe_permissions(gained: Permissions, lost: Permissions) -> String {
+    let line = |sign: char, permission: Permissions| {
+        let warning = if DANGEROUS_PERMISSIONS.contains(permission) {
+            " ⚠"
+        } else {
+            ""
+        };
+        format!("{}{}{}", sign, permission_name(permission), warning)
+    };
+    let lines: Vec<String> = gained
+        .iter()
+        .map(|permission| line('+', permission))
+        .chain(lost.iter().map(|permission| line('-', permission)))
+        .collect();
+    // Leaves room for the code block around it.
+    truncate(&lines.join("\n"), FIELD_LIMIT - 12)
+}
+
+fn describe_colour(colour: u32) -> String {
+    match colour {
+        0 => "*default*".to_string(),
+        colour => format!("#{}", Colour::new(colour).hex()),
+    }
+}
+
+fn role_embed(title: &str, colour: Colour, role_id: RoleId, name: &str) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(title)
+        .timestamp(Timestamp::now())
+        .colour(colour)
+        .field(
+            "Role",
+            format!(
+                "{} {} ({})",
+                role_id.mention(),
+                escape_markdown(name),
+                role_id
+            ),
+            false,
+        )
+}
+
+/// Adds the permission diff, flagging the embed if dangerous permissions were granted.
+fn with_permissions(embed: CreateEmbed, gained: Permissions, lost: Permissions) -> CreateEmbed {
+    if gained.is_empty() && lost.is_empty() {
+        return embed;
+    }
+    let mut embed = embed.field(
+        "Permissions",
+        format!("```diff\n{}\n```", describe_permissions(gained, lost)),
+        false,
+    );
+    let dangerous = gained.intersection(DANGEROUS_PERMISSIONS);
+    if !dangerous.is_empty() {
+        let names: Vec<&str> = dangerous.iter().map(permission_name).collect();
+        embed = embed.colour(Colour::RED).field(
+            ":warning: Dangerous Permissions Granted",
+            names.join(", "),
+            false,
+        );
+    }
+    embed
+}
+
+pub(super) async fn log_role_create(
+    ctx: &serenity::Context,
+    data: &Data,
+    role: &Role,
+) -> DoomResult<()> {
+    let mut embed = role_embed("Role Created", Colour::DARK_GREEN, role.id, &role.name)
+        .field("Colour", describe_colour(role.colour.0), true)
+        .field("Hoisted", if role.hoist { "Yes" } else { "No" }, true)
+        .field(
+            "Mentionable",
+            if role.mentionable { "Yes" } else { "No" },
+            true,
+        );
+    embed = with_permissions(embed, role.permissions, Permissions::empty());
+    send_attributed_log(
+        ctx,
+        data,
+        role.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Created by",
+            &[Action::Role(RoleAction::Create)],
+            role.id.get(),
+        ),
+    )
+    .await
+}
+
+pub(super) async fn log_role_delete(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    role_id: RoleId,
+    role: Option<&Role>,
+) -> DoomResult<()> {
+    let name = role.map_or("unknown", |role| &role.name);
+    let mut embed = role_embed("Role Deleted", Colour::RED, role_id, name);
+    if let Some(role) = role {
+        embed = embed.field("Colour", describe_colour(role.colour.0), true);
+        if !role.permissions.is_empty() {
+            embed = embed.field(
+                "Permissions",
+                format!(
+                    "```diff\n{}\n```",
+                    describe_permissions(Permissions::empty(), role.permissions)
+                ),
+                false,
+            );
+        }
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Deleted by",
+            &[Action::Role(RoleAction::Delete)],
+            role_id.get(),
+        ),
+    )
+    .await
+}
+
+/// Logs what changed about a role, compared to the cached role.
+pub(super) async fn log_role_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&Role>,
+    new: &Role,
+) -> DoomResult<()> {
+    let Some(old) = old else {
+        skip_uncached_update("role", new.id);
+        return Ok(());
+    };
+    let changes = detect_role_changes(&RoleState::from_role(old), &RoleState::from_role(new));
+    if changes.iter().all(RoleChange::is_position) {
+        return Ok(());
+    }
+
+    let mut embed = role_embed("Role Updated", Colour::BLUE, new.id, &new.name);
+    for change in &changes {
+        embed = match change {
+            RoleChange::Name { before, after } => embed.field(
+                "Name",
+                format!("{} → {}", escape_markdown(before), escape_markdown(after)),
+                false,
+            ),
+            RoleChange::Colour { before, after } => embed.field(
+                "Colour",
+                format!("{} → {}", describe_colour(*before), describe_colour(*after)),
+                true,
+            ),
+            RoleChange::Hoist(hoist) => {
+                embed.field("Hoisted", if *hoist { "Enabled" } else { "Disabled" }, true)
+            }
+            RoleChange::Mentionable(mentionable) => embed.field(
+                "Mentionable",
+                if *mentionable { "Enabled" } else { "Disabled" },
+                true,
+            ),
+            RoleChange::Position { before, after } => {
+                embed.field("Position", format!("{} → {}", before, after), true)
+            }
+            RoleChange::Permissions { gained, lost } => with_permissions(embed, *gained, *lost),
+        };
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        new.guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Updated by",
+            &[Action::Role(RoleAction::Update)],
+            new.id.get(),
+        ),
+    )
+    .await
+}
@@ -0,0 +1,475 @@
+use super::{send_attributed_log, send_log, skip_uncached_update, Attribution};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::util::discord::escape_markdown;
+use crate::Data;
+use poise::serenity_prelude::audit_log::{Action, EmojiAction, StickerAction};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, Colour, CreateEmbed, CreateMessage, Emoji, EmojiId,
+    ExplicitContentFilter, Guild, GuildId, Mentionable, PartialGuild, Sticker, StickerId,
+    Timestamp, VerificationLevel,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Discord's limit on the number of embeds per message.
+const MAX_EMBEDS: usize = 10;
+
+/// An emoji or sticker, as far as its changes are logged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Asset {
+    pub name: String,
+    pub image: Option<String>,
+}
+
+impl From<&Emoji> for Asset {
+    fn from(emoji: &Emoji) -> Self {
+        Asset {
+            name: emoji.name.clone(),
+            image: Some(emoji.url()),
+        }
+    }
+}
+
+impl From<&Sticker> for Asset {
+    fn from(sticker: &Sticker) -> Self {
+        Asset {
+            name: sticker.name.clone(),
+            image: sticker.image_url(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AssetChange {
+    Added(u64, Asset),
+    Removed(u64, Asset),
+    Renamed {
+        id: u64,
+        before: String,
+        after: Asset,
+    },
+}
+
+pub fn diff_assets(
+    before: &BTreeMap<u64, Asset>,
+    after: &BTreeMap<u64, Asset>,
+) -> Vec<AssetChange> {
+    let mut changes = vec![];
+    for (id, asset) in after {
+        match before.get(id) {
+            None => changes.push(AssetChange::Added(*id, asset.clone())),
+            Some(old) if old.name != asset.name => changes.push(AssetChange::Renamed {
+                id: *id,
+                before: old.name.clone(),
+                after: asset.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (id, asset) in before {
+        if !after.contains_key(id) {
+            changes.push(AssetChange::Removed(*id, asset.clone()));
+        }
+    }
+    changes
+}
+
+pub fn emoji_assets(emojis: &HashMap<EmojiId, Emoji>) -> BTreeMap<u64, Asset> {
+    emojis.iter().map(|(id, e)| (id.get(), e.into())).collect()
+}
+
+pub fn sticker_assets(stickers: &HashMap<StickerId, Sticker>) -> BTreeMap<u64, Asset> {
+    stickers
+        .iter()
+        .map(|(id, s)| (id.get(), s.into()))
+        .collect()
+}
+
+#[derive(Default)]
+struct Assets {
+    emojis: BTreeMap<u64, Asset>,
+    stickers: BTreeMap<u64, Asset>,
+}
+
+/// The emojis and stickers of each guild as of the last event, as Serenity updates its cache
+/// before handing out the event.
+#[derive(Default)]
+pub struct AssetCache {
+    guilds: Mutex<HashMap<GuildId, Assets>>,
+}
+
+impl AssetCache {
+    pub fn seed(&self, guild: &Guild) {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        guilds.insert(
+            guild.id,
+            Assets {
+                emojis: emoji_assets(&guild.emojis),
+                stickers: sticker_assets(&guild.stickers),
+            },
+        );
+    }
+
+    /// Stores the current emojis, returning the previous ones if known.
+    pub fn replace_emojis(
+        &self,
+        guild_id: GuildId,
+        emojis: BTreeMap<u64, Asset>,
+    ) -> Option<BTreeMap<u64, Asset>> {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        let known = guilds.contains_key(&guild_id);
+        let previous = std::mem::replace(&mut guilds.entry(guild_id).or_default().emojis, emojis);
+        known.then_some(previous)
+    }
+
+    /// Stores the current stickers, returning the previous ones if known.
+    pub fn replace_stickers(
+        &self,
+        guild_id: GuildId,
+        stickers: BTreeMap<u64, Asset>,
+    ) -> Option<BTreeMap<u64, Asset>> {
+        let mut guilds = self.guilds.lock().unwrap_or_else(|e| e.into_inner());
+        let known = guilds.contains_key(&guild_id);
+        let previous =
+            std::mem::replace(&mut guilds.entry(guild_id).or_default().stickers, stickers);
+        known.then_some(previous)
+    }
+}
+
+/// Which kind of asset changed, for titles and audit log lookups.
+#[derive(Clone, Copy)]
+pub enum AssetKind {
+    Emoji,
+    Sticker,
+}
+
+impl AssetKind {
+    fn name(self) -> &'static str {
+        match self {
+            AssetKind::Emoji => "Emoji",
+            AssetKind::Sticker => "Sticker",
+        }
+    }
+
+    fn action(self, change: &AssetChange) -> Action {
+        match (self, change) {
+            (AssetKind::Emoji, AssetChange::Added(..)) => Action::Emoji(EmojiAction::Create),
+            (AssetKind::Emoji, AssetChange::Removed(..)) => Action::Emoji(EmojiAction::Delete),
+            (AssetKind::Emoji, AssetChange::Renamed { .. }) => Action::Emoji(EmojiAction::Update),
+            (AssetKind::Sticker, AssetChange::Added(..)) => Action::Sticker(StickerAction::Create),
+            (AssetKind::Sticker, AssetChange::Removed(..)) => {
+                Action::Sticker(StickerAction::Delete)
+            }
+            (AssetKind::Sticker, AssetChange::Renamed { .. }) => {
+                Action::Sticker(StickerAction::Update)
+            }
+        }
+    }
+}
+
+/// Logs emojis or stickers being added, removed or renamed, one embed each.
+pub(super) async fn log_asset_changes(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    kind: AssetKind,
+    before: Option<BTreeMap<u64, Asset>>,
+    after: &BTreeMap<u64, Asset>,
+) -> DoomResult<()> {
+    let Some(before) = before else {
+        return Ok(());
+    };
+    let changes = diff_assets(&before, after);
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut embeds = vec![];
+    for change in &changes {
+        let (title, colour, id, asset) = match change {
+            AssetChange::Added(id, asset) => ("Added", Colour::DARK_GREEN, id, asset),
+            AssetChange::Removed(id, asset) => ("Removed", Colour::RED, id, asset),
+            AssetChange::Renamed { id, after, .. } => ("Renamed", Colour::BLUE, id, after),
+        };
+        let mut embed = CreateEmbed::new()
+            .title(format!("{} {}", kind.name(), title))
+            .timestamp(Timestamp::now())
+            .colour(colour)
+            .field("Name", escape_markdown(&asset.name), true)
+            .field("ID", id.to_string(), true);
+        if let AssetChange::Renamed { before, .. } = change {
+            embed = embed.field("Previous Name", escape_markdown(before), true);
+        }
+        if let Some(image) = &asset.image {
+            embed = embed.thumbnail(image);
+        }
+        // Each lookup waits for the audit log, so bulk changes go without.
+        if changes.len() == 1 {
+            return send_attributed_log(
+                ctx,
+                data,
+                guild_id,
+                LogCategory::ServerChanges,
+                CreateMessage::new(),
+                embed,
+                Attribution::new("Changed by", &[kind.action(change)], *id),
+            )
+            .await;
+        }
+        embeds.push(embed);
+    }
+
+    // Bulk changes are spread over as many messages as it takes.
+    for embeds in embeds.chunks(MAX_EMBEDS) {
+        send_log(
+            ctx,
+            data,
+            Some(guild_id),
+            LogCategory::ServerChanges,
+            CreateMessage::new().embeds(embeds.to_vec()),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// The server settings which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GuildSettings {
+    pub name: String,
+    /// Hash of the server icon.
+    pub icon: Option<String>,
+    pub verification_level: VerificationLevel,
+    pub explicit_content_filter: ExplicitContentFilter,
+    pub system_channel: Option<ChannelId>,
+    pub vanity_url: Option<String>,
+}
+
+impl GuildSettings {
+    pub fn from_guild(guild: &Guild) -> Self {
+        GuildSettings {
+            name: guild.name.clone(),
+            icon: guild.icon.map(|hash| hash.to_string()),
+            verification_level: guild.verification_level,
+            explicit_content_filter: guild.explicit_content_filter,
+            system_channel: guild.system_channel_id,
+            vanity_url: guild.vanity_url_code.clone(),
+        }
+    }
+
+    pub fn from_partial(guild: &PartialGuild) -> Self {
+        GuildSettings {
+            name: guild.name.clone(),
+            icon: guild.icon.map(|hash| hash.to_string()),
+            verification_level: guild.verification_level,
+            explicit_content_filter: guild.explicit_content_filter,
+            system_channel: guild.system_channel_id,
+            vanity_url: guild.vanity_url_code.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GuildChange {
+    Name {
+        before: String,
+        after: String,
+    },
+    Icon {
+        before: Option<String>,
+        after: Option<String>,
+    },
+    VerificationLevel {
+        before: VerificationLevel,
+        after: VerificationLevel,
+    },
+    ExplicitContentFilter {
+        before: ExplicitContentFilter,
+        after: ExplicitContentFilter,
+    },
+    SystemChannel {
+        before: Option<ChannelId>,
+        after: Option<ChannelId>,
+    },
+    VanityUrl {
+        before: Option<String>,
+        after: Option<String>,
+    },
+}
+
+pub fn detect_guild_changes(before: &GuildSettings, after: &GuildSettings) -> Vec<GuildChange> {
+    let mut changes = vec![];
+    if before.name != after.name {
+        changes.push(GuildChange::Name {
+            before: before.name.clone(),
+            after: after.name.clone(),
+        });
+    }
+    if before.icon != after.icon {
+        changes.push(GuildChange::Icon {
+            before: before.icon.clone(),
+            after: after.icon.clone(),
+        });
+    }
+    if before.verification_level != after.verification_level {
+        changes.push(GuildChange::VerificationLevel {
+            before: before.verification_level,
+            after: after.verification_level,
+        });
+    }
+    if before.explicit_content_filter != after.explicit_content_filter {
+        changes.push(GuildChange::ExplicitContentFilter {
+            before: before.explicit_content_filter,
+            after: after.explicit_content_filter,
+        });
+    }
+    if before.system_channel != after.system_channel {
+        changes.push(GuildChange::SystemChannel {
+            before: before.system_channel,
+            after: after.system_channel,
+        });
+    }
+    if before.vanity_url != after.vanity_url {
+        changes.push(GuildChange::VanityUrl {
+            before: before.vanity_url.clone(),
+            after: after.vanity_url.clone(),
+        });
+    }
+    changes
+}
+
+fn describe_verification_level(level: VerificationLevel) -> &'static str {
+    match level {
+        VerificationLevel::None => "None",
+        VerificationLevel::Low => "Low (verified email)",
+        VerificationLevel::Medium => "Medium (registered for 5 minutes)",
+        VerificationLevel::High => "High (member for 10 minutes)",
+        VerificationLevel::Higher => "Highest (verified phone)",
+        _ => "Unknown",
+    }
+}
+
+fn describe_content_filter(filter: ExplicitContentFilter) -> &'static str {
+    match filter {
+        ExplicitContentFilter::None => "Off",
+        ExplicitContentFilter::WithoutRole => "Members without roles",
+        ExplicitContentFilter::All => "All members",
+        _ => "Unknown",
+    }
+}
+
+fn icon_url(guild_id: GuildId, hash: &str) -> String {
+    let extension = if hash.starts_with("a_") {
+        "gif"
+    } else {
+        "webp"
+    };
+    format!(
+        "https://cdn.discordapp.com/icons/{}/{}.{}?size=1024",
+        guild_id, hash, extension
+    )
+}
+
+fn describe_optional(value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map_or("*none*".to_string(), |v| escape_markdown(v))
+}
+
+/// Logs changes of the server settings, compared to the cached server.
+pub(super) async fn log_guild_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&Guild>,
+    new: &PartialGuild,
+) -> DoomResult<()> {
+    let Some(old) = old else {
+        skip_uncached_update("server", new.id);
+        return Ok(());
+    };
+    let changes = detect_guild_changes(
+        &GuildSettings::from_guild(old),
+        &GuildSettings::from_partial(new),
+    );
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let guild_id = new.id;
+    let mut embed = CreateEmbed::new()
+        .title("Server Updated")
+        .timestamp(Timestamp::now())
+        .colour(Colour::BLUE);
+    for change in &changes {
+        embed = match change {
+            GuildChange::Name { before, after } => embed.field(
+                "Name",
+                format!("{} → {}", escape_markdown(before), escape_markdown(after)),
+                false,
+            ),
+            GuildChange::Icon { before, after } => {
+                let link = |label: &str, hash: &Option<String>| match hash {
+                    Some(hash) => format!("[{}]({})", label, icon_url(guild_id, hash)),
+                    None => "*none*".to_string(),
+                };
+                let embed = embed.field(
+                    "Icon",
+                    format!("{} → {}", link("Before", before), link("After", after)),
+                    false,
+                );
+                match after {
+                    Some(hash) => embed.thumbnail(icon_url(guild_id, hash)),
+                    None => embed,
+                }
+            }
+            GuildChange::VerificationLevel { before, after } => embed.field(
+                "Verification Level",
+                format!(
+                    "{} → {}",
+                    describe_verification_level(*before),
+                    describe_verification_level(*after)
+                ),
+                false,
+            ),
+            GuildChange::ExplicitContentFilter { before, after } => embed.field(
+                "Explicit Content Filter",
+                format!(
+                    "{} → {}",
+                    describe_content_filter(*before),
+                    describe_content_filter(*after)
+                ),
+                false,
+            ),
+            GuildChange::SystemChannel { before, after } => {
+                let describe = |c: &Option<ChannelId>| {
+                    c.map_or("*none*".to_string(), |c| c.mention().to_string())
+                };
+                embed.field(
+                    "System Channel",
+                    format!("{} → {}", describe(before), describe(after)),
+                    false,
+                )
+            }
+            GuildChange::VanityUrl { before, after } => embed.field(
+                "Vanity URL",
+                format!(
+                    "{} → {}",
+                    describe_optional(before),
+                    describe_optional(after)
+                ),
+                false,
+            ),
+        };
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        guild_id,
+        LogCategory::ServerChanges,
+        CreateMessage::new(),
+        embed,
+        Attribution::new("Updated by", &[Action::GuildUpdate], guild_id.get()),
+    )
+    .await
+}
@@ -0,0 +1,316 @@
+use super::{
+    is_ignored, send_attributed_log, send_log, skip_uncached_update, transcript_files, Attribution,
+};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::persistence::get_messages_by_channel;
+use crate::util::discord::{escape_markdown, join_limited, FIELD_LIMIT};
+use crate::Data;
+use poise::serenity_prelude::audit_log::{Action, ThreadAction};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, ChannelType, Colour, CreateEmbed, CreateMessage, ForumTagId,
+    GuildChannel, GuildId, Mentionable, PartialGuildChannel, Timestamp, UserId,
+};
+use std::collections::BTreeSet;
+
+/// The parts of a thread which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThreadState {
+    pub name: String,
+    pub archived: bool,
+    pub locked: bool,
+    /// Tags of forum posts.
+    pub tags: BTreeSet<ForumTagId>,
+}
+
+impl ThreadState {
+    pub fn from_thread(thread: &GuildChannel) -> Self {
+        let metadata = thread.thread_metadata.as_ref();
+        ThreadState {
+            name: thread.name.clone(),
+            archived: metadata.is_some_and(|m| m.archived),
+            locked: metadata.is_some_and(|m| m.locked),
+            tags: thread.applied_tags.iter().copied().collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ThreadChange {
+    Renamed {
+        before: String,
+        after: String,
+    },
+    Archived(bool),
+    Locked(bool),
+    Tags {
+        added: Vec<ForumTagId>,
+        removed: Vec<ForumTagId>,
+    },
+}
+
+pub fn detect_thread_changes(before: &ThreadState, after: &ThreadState) -> Vec<ThreadChange> {
+    let mut changes = vec![];
+    if before.name != after.name {
+        changes.push(ThreadChange::Renamed {
+            before: before.name.clone(),
+            after: after.name.clone(),
+        });
+    }
+    if before.archived != after.archived {
+        changes.push(ThreadChange::Archived(after.archived));
+    }
+    if before.locked != after.locked {
+        changes.push(ThreadChange::Locked(after.locked));
+    }
+    let added: Vec<ForumTagId> = after.tags.difference(&before.tags).copied().collect();
+    let removed: Vec<ForumTagId> = before.tags.difference(&after.tags).copied().collect();
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(ThreadChange::Tags { added, removed });
+    }
+    changes
+}
+
+/// Whether the thread is a post of a forum channel, as far as cached.
+fn is_forum_post(ctx: &serenity::Context, guild_id: GuildId, parent: Option<ChannelId>) -> bool {
+    let Some(parent) = parent else {
+        return false;
+    };
+    ctx.cache
+        .guild(guild_id)
+        .and_then(|guild| guild.channels.get(&parent).map(|c| c.kind))
+        .is_some_and(|kind| kind == ChannelType::Forum)
+}
+
+/// Looks up the names of forum tags in the cached forum, falling back to their ids.
+fn tag_names(
+    ctx: &serenity::Context,
+    guild_id: GuildId,
+    parent: Option<ChannelId>,
+    tags: &[ForumTagId],
+) -> Vec<String> {
+    let guild = ctx.cache.guild(guild_id);
+    let forum = guild
+        .as_ref()
+        .zip(parent)
+        .and_then(|(guild, parent)| guild.channels.get(&parent));
+    tags.iter()
+        .map(|id| {
+            forum
+                .and_then(|forum| forum.available_tags.iter().find(|tag| tag.id == *id))
+                .map_or(id.to_string(), |tag| escape_markdown(&tag.name))
+        })
+        .collect()
+}
+
+fn thread_embed(
+    title: &str,
+    colour: Colour,
+    thread_id: ChannelId,
+    name: Option<&str>,
+    parent: Option<ChannelId>,
+) -> CreateEmbed {
+    let thread = match name {
+        Some(name) => format!(
+            "{} {} ({})",
+            thread_id.mention(),
+            escape_markdown(name),
+            thread_id
+        ),
+        None => format!("{} ({})", thread_id.mention(), thread_id),
+    };
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .timestamp(Timestamp::now())
+        .colour(colour)
+        .field("Thread", thread, false);
+    if let Some(parent) = parent {
+        embed = embed.field("Channel", parent.mention().to_string(), true);
+    }
+    embed
+}
+
+pub(super) async fn log_thread_create(
+    ctx: &serenity::Context,
+    data: &Data,
+    thread: &GuildChannel,
+) -> DoomResult<()> {
+    let guild_id = thread.guild_id;
+    if is_ignored(ctx, data, guild_id, thread.id, thread.owner_id, None) {
+        return Ok(());
+    }
+    let forum_post = is_forum_post(ctx, guild_id, thread.parent_id);
+    let title = if forum_post {
+        "Forum Post Created"
+    } else {
+        "Thread Created"
+    };
+    let mut embed = thread_embed(
+        title,
+        Colour::DARK_GREEN,
+        thread.id,
+        Some(&thread.name),
+        thread.parent_id,
+    );
+    if let Some(owner) = thread.owner_id {
+        embed = embed.field(
+            "Created by",
+            format!("{} ({})", owner.mention(), owner),
+            true,
+        );
+    }
+    if thread.kind == ChannelType::PrivateThread {
+        embed = embed.field("Private", "Yes", true);
+    }
+    if !thread.applied_tags.is_empty() {
+        let tags = tag_names(ctx, guild_id, thread.parent_id, &thread.applied_tags);
+        embed = embed.field("Tags", join_limited(&tags, ", ", FIELD_LIMIT), false);
+    }
+
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::Threads,
+        CreateMessage::new().embed(embed),
+    )
+    .await
+}
+
+/// Logs renames, (un)archiving, (un)locking and tag changes of a thread.
+pub(super) async fn log_thread_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&GuildChannel>,
+    new: &GuildChannel,
+) -> DoomResult<()> {
+    let guild_id = new.guild_id;
+    if is_ignored(ctx, data, guild_id, new.id, None, Some(&[])) {
+        return Ok(());
+    }
+    let Some(old) = old else {
+        skip_uncached_update("thread", new.id);
+        return Ok(());
+    };
+    let changes = detect_thread_changes(
+        &ThreadState::from_thread(old),
+        &ThreadState::from_thread(new),
+    );
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let title = if is_forum_post(ctx, guild_id, new.parent_id) {
+        "Forum Post Updated"
+    } else {
+        "Thread Updated"
+    };
+    let mut embed = thread_embed(title, Colour::BLUE, new.id, Some(&new.name), new.parent_id);
+    for change in &changes {
+        embed = match change {
+            ThreadChange::Renamed { before, after } => embed.field(
+                "Name",
+                format!("{} → {}", escape_markdown(before), escape_markdown(after)),
+                false,
+            ),
+            ThreadChange::Archived(archived) => {
+                embed.field("Archived", if *archived { "Yes" } else { "No" }, true)
+            }
+            ThreadChange::Locked(locked) => {
+                embed.field("Locked", if *locked { "Yes" } else { "No" }, true)
+            }
+            ThreadChange::Tags { added, removed } => {
+                let mut tags: Vec<String> = tag_names(ctx, guild_id, new.parent_id, added)
+                    .into_iter()
+                    .map(|name| format!("+{}", name))
+                    .collect();
+                tags.extend(
+                    tag_names(ctx, guild_id, new.parent_id, removed)
+                        .into_iter()
+                        .map(|name| format!("-{}", name)),
+                );
+                embed.field("Tags", join_limited(&tags, ", ", FIELD_LIMIT), false)
+            }
+        };
+    }
+    send_attributed_log(
+        ctx,
+        data,
+        guild_id,
+        LogCategory::Threads,
+        CreateMessage::new(),
+        embed,
+        Attribution::new(
+            "Updated by",
+            &[Action::Thread(ThreadAction::Update)],
+            new.id.get(),
+        ),
+    )
+    .await
+}
+
+/// Logs the deletion of a thread, attaching a transcript of its stored messages.
+pub(super) async fn log_thread_delete(
+    ctx: &serenity::Context,
+    data: &Data,
+    thread: &PartialGuildChannel,
+    full: Option<&GuildChannel>,
+) -> DoomResult<()> {
+    let (guild_id, thread_id, parent) = (thread.guild_id, thread.id, thread.parent_id);
+    // The thread is gone from the cache already, so its parent decides.
+    if is_ignored(ctx, data, guild_id, parent, None, Some(&[])) {
+        return Ok(());
+    }
+    let mut messages = data
+        .db
+        .run(move |pool| get_messages_by_channel(pool, thread_id.get()))
+        .await?;
+    messages.retain(|message| {
+        let author = UserId::new(message.author as u64);
+        !is_ignored(ctx, data, guild_id, parent, Some(author), None)
+    });
+
+    let name = full.map(|thread| thread.name.as_str());
+    let title = if is_forum_post(ctx, guild_id, Some(parent)) {
+        "Forum Post Deleted"
+    } else {
+        "Thread Deleted"
+    };
+    let mut embed = thread_embed(title, Colour::RED, thread_id, name, Some(parent)).field(
+        "Stored Messages",
+        messages.len().to_string(),
+        true,
+    );
+    if let Some(owner) = full.and_then(|thread| thread.owner_id) {
+        embed = embed.field(
+            "Created by",
+            format!("{} ({})", owner.mention(), owner),
+            true,
+        );
+    }
+    let mut message = CreateMessage::new();
+    if !messages.is_empty() {
+        let title = format!(
+            "Deleted thread {} ({}) at {}",
+            name.unwrap_or("unknown-thread"),
+            thread_id,
+            Timestamp::now().format("%Y-%m-%d %H:%M:%S UTC")
+        );
+        message = message.add_files(transcript_files(ctx, &title, thread_id, &messages).await);
+    }
+
+    send_attributed_log(
+        ctx,
+        data,
+        guild_id,
+        LogCategory::Threads,
+        message,
+        embed,
+        Attribution::new(
+            "Deleted by",
+            &[Action::Thread(ThreadAction::Delete)],
+            thread_id.get(),
+        ),
+    )
+    .await
+}
@@ -0,0 +1,230 @@
+use super::{is_ignored, send_attributed_log, send_log, Attribution};
+use crate::config::LogCategory;
+use crate::error::DoomResult;
+use crate::persistence::{end_voice_session, start_voice_session};
+use crate::util::discord::format_duration;
+use crate::Data;
+use poise::serenity_prelude::audit_log::{Action, MemberAction};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId,
+    Mentionable, Timestamp, UserId, VoiceState,
+};
+
+/// The parts of a voice state which are logged when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VoiceSnapshot {
+    pub channel_id: Option<ChannelId>,
+    pub mute: bool,
+    pub deaf: bool,
+    pub stream: bool,
+    pub video: bool,
+}
+
+impl VoiceSnapshot {
+    pub fn from_state(state: &VoiceState) -> Self {
+        VoiceSnapshot {
+            channel_id: state.channel_id,
+            mute: state.mute,
+            deaf: state.deaf,
+            stream: state.self_stream.unwrap_or_default(),
+            video: state.self_video,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VoiceChange {
+    Joined(ChannelId),
+    Left(ChannelId),
+    Moved { from: ChannelId, to: ChannelId },
+    ServerMuted(bool),
+    ServerDeafened(bool),
+    Stream(bool),
+    Video(bool),
+}
+
+impl VoiceChange {
+    /// Server mutes and deafens are the only changes moderators are logged for.
+    fn is_moderated(&self) -> bool {
+        matches!(
+            self,
+            VoiceChange::ServerMuted(_) | VoiceChange::ServerDeafened(_)
+        )
+    }
+}
+
+/// Diffs two voice states. Mutes, streams and the like are only compared while the user stays
+/// connected, as they are reset or carried over when joining and leaving.
+pub fn detect_voice_changes(before: &VoiceSnapshot, after: &VoiceSnapshot) -> Vec<VoiceChange> {
+    let mut changes = vec![];
+    match (before.channel_id, after.channel_id) {
+        (None, Some(to)) => changes.push(VoiceChange::Joined(to)),
+        (Some(from), None) => changes.push(VoiceChange::Left(from)),
+        (Some(from), Some(to)) if from != to => changes.push(VoiceChange::Moved { from, to }),
+        _ => {}
+    }
+    if before.channel_id.is_none() || after.channel_id.is_none() {
+        return changes;
+    }
+
+    if before.mute != after.mute {
+        changes.push(VoiceChange::ServerMuted(after.mute));
+    }
+    if before.deaf != after.deaf {
+        changes.push(VoiceChange::ServerDeafened(after.deaf));
+    }
+    if before.stream != after.stream {
+        changes.push(VoiceChange::Stream(after.stream));
+    }
+    if before.video != after.video {
+        changes.push(VoiceChange::Video(after.video));
+    }
+    changes
+}
+
+/// Closes the open session of the user, noting how long it lasted.
+async fn end_session(
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    embed: CreateEmbed,
+) -> DoomResult<CreateEmbed> {
+    let now = Timestamp::now().naive_utc();
+    let joined = data
+        .db
+        .run(move |pool| end_voice_session(pool, guild_id.get(), user_id.get(), now))
+        .await?;
+    Ok(match joined {
+        Some(joined) => embed.field("Duration", format_duration(now - joined), true),
+        None => embed,
+    })
+}
+
+async fn start_session(
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    channel_id: ChannelId,
+) -> DoomResult<()> {
+    let now = Timestamp::now().naive_utc();
+    data.db
+        .run(move |pool| {
+            start_voice_session(pool, guild_id.get(), user_id.get(), channel_id.get(), now)
+        })
+        .await
+}
+
+pub(super) async fn log_voice_update(
+    ctx: &serenity::Context,
+    data: &Data,
+    old: Option<&VoiceState>,
+    new: &VoiceState,
+) -> DoomResult<()> {
+    let Some(guild_id) = new.guild_id else {
+        return Ok(());
+    };
+    let user_id = new.user_id;
+    let before = old.map(VoiceSnapshot::from_state).unwrap_or_default();
+    let changes = detect_voice_changes(&before, &VoiceSnapshot::from_state(new));
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let (title, colour) = match changes[0] {
+        VoiceChange::Joined(_) => ("Voice Channel Joined", Colour::DARK_GREEN),
+        VoiceChange::Left(_) => ("Voice Channel Left", Colour::DARK_GREY),
+        VoiceChange::Moved { .. } => ("Voice Channel Switched", Colour::BLUE),
+        _ => ("Voice State Updated", Colour::BLURPLE),
+    };
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .timestamp(Timestamp::now())
+        .colour(colour)
+        .field(
+            "Member",
+            format!("{} ({})", user_id.mention(), user_id),
+            false,
+        );
+    if let Some(member) = &new.member {
+        embed = embed
+            .thumbnail(member.face())
+            .footer(CreateEmbedFooter::new(&member.user.name).icon_url(member.face()));
+    }
+
+    let mut channels = vec![];
+    for change in &changes {
+        match change {
+            VoiceChange::Joined(channel) => {
+                channels.push(*channel);
+                embed = embed.field("Channel", channel.mention().to_string(), true);
+                start_session(data, guild_id, user_id, *channel).await?;
+            }
+            VoiceChange::Left(channel) => {
+                channels.push(*channel);
+                embed = embed.field("Channel", channel.mention().to_string(), true);
+                embed = end_session(data, guild_id, user_id, embed).await?;
+            }
+            VoiceChange::Moved { from, to } => {
+                channels.extend([*from, *to]);
+                embed = embed.field("From", from.mention().to_string(), true).field(
+                    "To",
+                    to.mention().to_string(),
+                    true,
+                );
+                embed = end_session(data, guild_id, user_id, embed).await?;
+                start_session(data, guild_id, user_id, *to).await?;
+            }
+            VoiceChange::ServerMuted(muted) => {
+                let state = if *muted { "Muted" } else { "Unmuted" };
+                embed = embed.field("Server Mute", state, true);
+            }
+            VoiceChange::ServerDeafened(deafened) => {
+                let state = if *deafened { "Deafened" } else { "Undeafened" };
+                embed = embed.field("Server Deafen", state, true);
+            }
+            VoiceChange::Stream(streaming) => {
+                let state = if *streaming { "Started" } else { "Stopped" };
+                embed = embed.field("Stream", state, true);
+            }
+            VoiceChange::Video(video) => {
+                let state = if *video { "Started" } else { "Stopped" };
+                embed = embed.field("Video", state, true);
+            }
+        }
+    }
+
+    // Sessions are kept regardless, so the history stays complete.
+    let channel = channels.last().copied().or(new.channel_id);
+    let roles = new.member.as_ref().map(|member| member.roles.as_slice());
+    if let Some(channel) = channel {
+        if is_ignored(ctx, data, guild_id, channel, Some(user_id), roles) {
+            return Ok(());
+        }
+    }
+
+    if changes.iter().any(VoiceChange::is_moderated) {
+        return send_attributed_log(
+            ctx,
+            data,
+            guild_id,
+            LogCategory::Voice,
+            CreateMessage::new(),
+            embed,
+            Attribution::new(
+                "Updated by",
+                &[Action::Member(MemberAction::Update)],
+                user_id.get(),
+            ),
+        )
+        .await;
+    }
+
+    send_log(
+        ctx,
+        data,
+        Some(guild_id),
+        LogCategory::Voice,
+        CreateMessage::new().embed(embed),
+    )
+    .await
+}
@@ -1,16 +1,28 @@
+mod archive;
+mod audit;
 mod commands;
+mod config;
+mod diff;
 mod error;
 mod event;
 mod log;
 mod persistence;
 #[cfg(test)]
 mod tests;
+mod transcript;
 mod util;
 
+use crate::archive::{ArchiveConfig, AttachmentArchive};
+use crate::audit::DeletionTracker;
+use crate::config::{ConfigCache, LogCategory};
+use crate::error::{DoomError, DoomResult};
+use crate::event::automod::AutoModCache;
+use crate::event::invite::InviteCache;
+use crate::event::server::AssetCache;
 use crate::log::setup_logger;
-use crate::persistence::{
-    establish_connection, sqlite_pool_handler, SqlitePool, SqlitePooledConnection,
-};
+use crate::persistence::database::Database;
+use crate::persistence::{establish_connection, sqlite_pool_handler, SqlitePooledConnection};
+use crate::util::env::{optional_var, required_var, var_or};
 use ::log::{error, info};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenvy::dotenv;
@@ -18,15 +30,22 @@ use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::{ChannelId, CreateMessage};
 use serenity::GatewayIntents;
 use std::error::Error as StdError;
+use std::num::NonZeroU64;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal;
 
 // User Data
+#[derive(Clone)]
 pub struct Data {
-    log_channel: ChannelId,
+    config: Arc<ConfigCache>,
     environment: String,
-    pool: SqlitePool,
+    db: Database,
+    archive: Arc<AttachmentArchive>,
+    deletions: Arc<DeletionTracker>,
+    assets: Arc<AssetCache>,
+    invites: Arc<InviteCache>,
+    automod: Arc<AutoModCache>,
 }
 pub type Error = Box<dyn StdError + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
@@ -53,28 +72,47 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> DoomResult<()> {
     dotenv().ok();
-    let token = std::env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
-    let log_channel = std::env::var("LOG_CHANNEL")
-        .expect("Expected a log channel in the environment")
-        .parse()
-        .expect("Channel ID: Not a proper Discord Snowflake");
-    let environment = std::env::var("ENV").unwrap_or("production".to_string());
+    let token: String = required_var("DISCORD_TOKEN")?;
+    let log_channel = optional_var::<NonZeroU64>("LOG_CHANNEL")?.map(ChannelId::from);
+    let environment = var_or("ENV", "production".to_string())?;
     info!("Environment is set up");
 
-    let pool = establish_connection();
-    let connection = sqlite_pool_handler(&pool).expect("Pooled Connection failed.");
-    run_migrations(connection).expect("Database should be initialize-able.");
+    let pool = establish_connection()?;
+    let connection = sqlite_pool_handler(&pool)?;
+    run_migrations(connection)
+        .map_err(|why| DoomError::database(format!("Migrations failed: {}", why)))?;
     setup_logger().expect("Failed to initialize logger");
     info!("Database & Logging are available.");
 
-    let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
+    let config = Arc::new(ConfigCache::load(&pool, log_channel)?);
+    let db = Database::new(pool);
+    let archive = AttachmentArchive::new(ArchiveConfig::from_env()?);
+
+    let mut intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
+    // Privileged, so it has to be enabled for the bot in the developer portal as well.
+    if var_or("LOG_MEMBERS", true)? {
+        intents |= GatewayIntents::GUILD_MEMBERS;
+    }
+    // Part of the non-privileged intents, but busy servers may not want voice activity at all.
+    if !var_or("LOG_VOICE", true)? {
+        intents.remove(GatewayIntents::GUILD_VOICE_STATES);
+    }
 
     let framework_environment = environment.clone();
+    let framework_config = config.clone();
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![commands::changelog()],
+            commands: vec![
+                commands::changelog(),
+                commands::config(),
+                commands::ignore(),
+                commands::invites(),
+                commands::modlog(),
+                commands::revisions(),
+                commands::voice_history(),
+            ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("$".into()),
                 edit_tracker: Some(Arc::new(poise::EditTracker::for_timespan(
@@ -96,18 +134,27 @@ async fn main() {
                     poise::builtins::register_globally(ctx, commands).await?;
                 }
                 Ok(Data {
-                    log_channel: ChannelId::new(log_channel),
+                    config: framework_config,
                     environment: framework_environment,
-                    pool,
+                    db,
+                    archive: Arc::new(archive),
+                    deletions: Arc::default(),
+                    assets: Arc::default(),
+                    invites: Arc::default(),
+                    automod: Arc::default(),
                 })
             })
         })
         .build();
 
+    // Cached messages are what non-content edits (i.e. components) get compared against
+    let mut cache_settings = serenity::cache::Settings::default();
+    cache_settings.max_messages = var_or("MESSAGE_CACHE_SIZE", 100)?;
+
     let mut client = serenity::ClientBuilder::new(token, intents)
+        .cache_settings(cache_settings)
         .framework(framework)
-        .await
-        .expect("Error creating client");
+        .await?;
 
     let ctrl_c = signal::ctrl_c();
     tokio::select! {
@@ -115,8 +162,11 @@ async fn main() {
             info!("Shutting down...");
             if environment != "develop" {
                 let message = CreateMessage::default().content("Goodbye :saluting_face:");
-                client.http.send_message(ChannelId::new(log_channel), vec![], &message)
-                    .await.expect("Couldn't send Goodbye Message...");
+                for channel in config.log_channels(LogCategory::BotStatus) {
+                    if let Err(why) = client.http.send_message(channel, vec![], &message).await {
+                        error!("Couldn't send Goodbye Message to {}: {}", channel, why);
+                    }
+                }
             }
             client.shard_manager.shutdown_all().await;
         }
@@ -126,4 +176,5 @@ async fn main() {
             }
         }
     }
+    Ok(())
 }
@@ -0,0 +1,92 @@
+use crate::error::{DoomError, DoomResult};
+use crate::persistence::models::Message;
+use crate::persistence::{create_messages, SqlitePool};
+use log::{error, warn};
+use tokio::sync::{mpsc, oneshot};
+
+/// Upper bound of messages the writer inserts within a single transaction.
+const BATCH_SIZE: usize = 256;
+const WRITER_STOPPED: &str = "Database writer stopped.";
+
+struct PendingMessage {
+    message: Message,
+    done: oneshot::Sender<DoomResult<()>>,
+}
+
+/// Async facade over the persistence layer.
+///
+/// Diesel is blocking, so queries run on tokio's blocking thread pool, while new messages are
+/// handed to a dedicated writer thread which inserts whatever has queued up in one transaction.
+#[derive(Clone)]
+pub struct Database {
+    pool: SqlitePool,
+    writer: mpsc::Sender<PendingMessage>,
+}
+
+impl Database {
+    pub fn new(pool: SqlitePool) -> Self {
+        let (writer, receiver) = mpsc::channel(BATCH_SIZE * 4);
+        let writer_pool = pool.clone();
+        std::thread::Builder::new()
+            .name("database-writer".to_string())
+            .spawn(move || write_messages(writer_pool, receiver))
+            .expect("Database writer should be spawnable.");
+        Database { pool, writer }
+    }
+
+    /// Runs blocking persistence code without stalling the async runtime.
+    pub async fn run<F, T>(&self, query: F) -> DoomResult<T>
+    where
+        F: FnOnce(&SqlitePool) -> DoomResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || query(&pool))
+            .await
+            .map_err(|why| DoomError::database(format!("Database task failed: {}", why)))?
+    }
+
+    /// Queues a message for the writer and waits until its batch has been committed.
+    pub async fn create_message(&self, message: Message) -> DoomResult<()> {
+        let (done, committed) = oneshot::channel();
+        self.writer
+            .send(PendingMessage { message, done })
+            .await
+            .map_err(|_| DoomError::database(WRITER_STOPPED))?;
+        committed
+            .await
+            .map_err(|_| DoomError::database(WRITER_STOPPED))?
+    }
+}
+
+fn write_messages(pool: SqlitePool, mut receiver: mpsc::Receiver<PendingMessage>) {
+    while let Some(first) = receiver.blocking_recv() {
+        let mut batch = vec![first];
+        while batch.len() < BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(pending) => batch.push(pending),
+                Err(_) => break,
+            }
+        }
+
+        let messages: Vec<&Message> = batch.iter().map(|pending| &pending.message).collect();
+        match create_messages(&pool, &messages) {
+            Ok(_) => {
+                for pending in batch {
+                    let _ = pending.done.send(Ok(()));
+                }
+            }
+            Err(why) => {
+                // A single bad message (i.e. a duplicate) mustn't take its whole batch down.
+                warn!("Batch of {} messages failed: {}", batch.len(), why);
+                for pending in batch {
+                    let result = create_messages(&pool, &[&pending.message]).map(|_| ());
+                    if let Err(why) = &result {
+                        error!("Error creating message {}: {}", pending.message.id, why);
+                    }
+                    let _ = pending.done.send(result);
+                }
+            }
+        }
+    }
+}
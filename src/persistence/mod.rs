@@ -1,110 +1,589 @@
+pub(crate) mod database;
 pub(crate) mod models;
 pub(crate) mod schema;
 
+use crate::error::DoomResult;
 use crate::persistence::models::*;
+use crate::util::env::required_var;
 use crate::util::UNKNOWN_USER;
+use chrono::NaiveDateTime;
+use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, PoolError};
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, PoolError};
 use diesel::SqliteConnection;
 use r2d2::{Error, Pool, PooledConnection};
+use std::collections::BTreeSet;
 
 pub type SqlitePool = Pool<ConnectionManager<SqliteConnection>>;
 pub type SqlitePooledConnection = PooledConnection<ConnectionManager<SqliteConnection>>;
 
-pub fn establish_connection() -> SqlitePool {
-    let database_url =
-        std::env::var("DATABASE_URL").expect("Expected a database url in the environment");
-    init_pool(&database_url).expect("Failed to create Database Pool")
+pub fn establish_connection() -> DoomResult<SqlitePool> {
+    let database_url: String = required_var("DATABASE_URL")?;
+    Ok(init_pool(&database_url)?)
+}
+
+/// Lets concurrent connections wait on each other instead of failing with "database is locked".
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, connection: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        connection
+            .batch_execute(
+                "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA busy_timeout = 5000;",
+            )
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
 }
 
 pub fn init_pool(database_url: &str) -> Result<SqlitePool, PoolError> {
     let manager = ConnectionManager::<SqliteConnection>::new(database_url);
-    Pool::builder().build(manager)
+    Pool::builder()
+        .connection_customizer(Box::new(ConnectionOptions))
+        .build(manager)
 }
 
 pub fn sqlite_pool_handler(pool: &SqlitePool) -> Result<SqlitePooledConnection, Error> {
     pool.get()
 }
 
-pub fn get_author_from_message(pool: &SqlitePool, message_id: u64) -> u64 {
+pub fn get_author_from_message(pool: &SqlitePool, message_id: u64) -> DoomResult<u64> {
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
-    messages
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let message = messages
         .find(message_id as i64)
         .select(Message::as_select())
         .first(connection)
-        .optional()
-        .expect("Error loading messages")
-        .map_or(UNKNOWN_USER, |m| m.author as u64)
+        .optional()?;
+    Ok(message.map_or(UNKNOWN_USER, |m| m.author as u64))
 }
 
-pub fn get_message_content_by_id(pool: &SqlitePool, message_id: u64) -> String {
+pub fn get_message_by_id(pool: &SqlitePool, message_id: u64) -> DoomResult<Option<Message>> {
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
-    messages
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(messages
         .find(message_id as i64)
         .select(Message::as_select())
         .first(connection)
-        .optional()
-        .expect("Error loading messages")
-        .map_or("<unknown message>".to_string(), |m| m.content.clone())
+        .optional()?)
 }
 
-pub fn get_message_content_and_author_by_id(pool: &SqlitePool, message_id: u64) -> (u64, String) {
+pub fn get_messages_by_ids(pool: &SqlitePool, message_ids: &[u64]) -> DoomResult<Vec<Message>> {
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
-    let results = messages
-        .find(message_id as i64)
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let message_ids: Vec<i64> = message_ids.iter().map(|m| *m as i64).collect();
+    Ok(messages
+        .filter(id.eq_any(message_ids))
+        .order((created_at.asc(), id.asc()))
         .select(Message::as_select())
-        .first(connection)
-        .optional()
-        .expect("Error loading messages");
+        .load(connection)?)
+}
 
-    results.map_or((UNKNOWN_USER, "<unknown_message>".to_string()), |m| {
-        (m.author as u64, m.content.clone())
-    })
+pub fn get_messages_by_channel(pool: &SqlitePool, channel: u64) -> DoomResult<Vec<Message>> {
+    use crate::persistence::schema::messages::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(messages
+        .filter(channel_id.eq(channel as i64))
+        .order((created_at.asc(), id.asc()))
+        .select(Message::as_select())
+        .load(connection)?)
 }
 
-pub fn exists_message(pool: &SqlitePool, message_id: u64) -> bool {
+pub fn get_message_count(pool: &SqlitePool) -> DoomResult<i64> {
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
-    messages
-        .find(message_id as i64)
-        .count()
-        .get_result::<i64>(connection)
-        .expect("Error loading messages.")
-        > 0
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(messages.count().get_result::<i64>(connection)?)
 }
 
-pub fn get_message_count(pool: &SqlitePool) -> i64 {
+pub fn get_channel_message_count(pool: &SqlitePool, channel: u64) -> DoomResult<i64> {
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
-    messages
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(messages
+        .filter(channel_id.eq(channel as i64))
         .count()
-        .get_result::<i64>(connection)
-        .expect("Error loading messages.")
+        .get_result::<i64>(connection)?)
 }
 
-pub fn create_message(pool: &SqlitePool, message_id: u64, author_id: u64, text: String) -> Message {
+/// Inserts messages together with their first revision, all within one transaction.
+pub fn create_messages(pool: &SqlitePool, new_messages: &[&Message]) -> DoomResult<usize> {
+    use crate::persistence::schema::message_revisions;
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
-    let new_message = Message {
-        id: message_id as i64,
-        author: author_id as i64,
-        content: text,
-    };
-    diesel::insert_into(messages)
-        .values(&new_message)
-        .execute(connection)
-        .expect("Error creating message.");
-    new_message
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let first_revisions: Vec<NewMessageRevision> = new_messages
+        .iter()
+        .map(|message| NewMessageRevision {
+            message_id: message.id,
+            revision: 1,
+            content: message.content.clone(),
+            edited_at: message.edited_at.unwrap_or(message.created_at),
+        })
+        .collect();
+    let inserted = connection.transaction(|connection| {
+        let mut inserted = 0;
+        for message in new_messages {
+            inserted += diesel::insert_into(messages)
+                .values(*message)
+                .execute(connection)?;
+        }
+        diesel::insert_into(message_revisions::table)
+            .values(&first_revisions)
+            .execute(connection)?;
+        QueryResult::Ok(inserted)
+    })?;
+    Ok(inserted)
+}
+
+/// Stores `text` as the newest revision of a message and makes it the message's current content.
+/// Returns the number of the freshly created revision.
+pub fn add_message_revision(
+    pool: &SqlitePool,
+    message_id: u64,
+    text: String,
+    edit_timestamp: NaiveDateTime,
+) -> DoomResult<i32> {
+    use crate::persistence::schema::message_revisions::dsl as revisions;
+    use crate::persistence::schema::messages::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let revision = connection.transaction(|connection| {
+        let latest = revisions::message_revisions
+            .filter(revisions::message_id.eq(message_id as i64))
+            .select(diesel::dsl::max(revisions::revision))
+            .first::<Option<i32>>(connection)?
+            .unwrap_or(0);
+        let new_revision = NewMessageRevision {
+            message_id: message_id as i64,
+            revision: latest + 1,
+            content: text.clone(),
+            edited_at: edit_timestamp,
+        };
+        diesel::insert_into(revisions::message_revisions)
+            .values(&new_revision)
+            .execute(connection)?;
+        diesel::update(messages.find(message_id as i64))
+            .set((content.eq(text), edited_at.eq(Some(new_revision.edited_at))))
+            .execute(connection)?;
+        QueryResult::Ok(new_revision.revision)
+    })?;
+    Ok(revision)
 }
 
-pub fn update_message_content(pool: &SqlitePool, message_id: u64, text: String) {
+pub fn update_message_state(
+    pool: &SqlitePool,
+    message_id: u64,
+    new_flags: i64,
+    is_pinned: bool,
+) -> DoomResult<()> {
     use crate::persistence::schema::messages::dsl::*;
-    let connection = &mut sqlite_pool_handler(pool).expect("Pooled Connection failed.");
+    let connection = &mut sqlite_pool_handler(pool)?;
     diesel::update(messages.find(message_id as i64))
-        .set(content.eq(text))
-        .execute(connection)
-        .expect("Error updating message.");
+        .set((flags.eq(new_flags), pinned.eq(is_pinned)))
+        .execute(connection)?;
+    Ok(())
+}
+
+pub fn get_message_revisions(
+    pool: &SqlitePool,
+    message_id: u64,
+) -> DoomResult<Vec<MessageRevision>> {
+    use crate::persistence::schema::message_revisions::dsl;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(dsl::message_revisions
+        .filter(dsl::message_id.eq(message_id as i64))
+        .order(dsl::revision.asc())
+        .select(MessageRevision::as_select())
+        .load(connection)?)
+}
+
+pub fn create_attachment(pool: &SqlitePool, attachment: Attachment) -> DoomResult<Attachment> {
+    use crate::persistence::schema::attachments::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::insert_into(attachments)
+        .values(&attachment)
+        .execute(connection)?;
+    Ok(attachment)
+}
+
+pub fn get_attachments_by_message(pool: &SqlitePool, message: u64) -> DoomResult<Vec<Attachment>> {
+    use crate::persistence::schema::attachments::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(attachments
+        .filter(message_id.eq(message as i64))
+        .order(id.asc())
+        .select(Attachment::as_select())
+        .load(connection)?)
+}
+
+pub fn mark_attachments_removed(
+    pool: &SqlitePool,
+    ids: &[i64],
+    at: NaiveDateTime,
+) -> DoomResult<()> {
+    use crate::persistence::schema::attachments::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::update(attachments.filter(id.eq_any(ids)))
+        .set(removed_at.eq(Some(at)))
+        .execute(connection)?;
+    Ok(())
+}
+
+pub fn get_guild_configs(pool: &SqlitePool) -> DoomResult<Vec<GuildConfig>> {
+    use crate::persistence::schema::guild_config::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(guild_config
+        .select(GuildConfig::as_select())
+        .load(connection)?)
+}
+
+pub fn save_guild_config(pool: &SqlitePool, config: GuildConfig) -> DoomResult<GuildConfig> {
+    use crate::persistence::schema::guild_config::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::insert_into(guild_config)
+        .values(&config)
+        .on_conflict(guild_id)
+        .do_update()
+        .set(&config)
+        .execute(connection)?;
+    Ok(config)
+}
+
+pub fn get_log_routes(pool: &SqlitePool) -> DoomResult<Vec<LogRoute>> {
+    use crate::persistence::schema::log_routes::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(log_routes.select(LogRoute::as_select()).load(connection)?)
+}
+
+pub fn save_log_route(pool: &SqlitePool, route: LogRoute) -> DoomResult<LogRoute> {
+    use crate::persistence::schema::log_routes::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::insert_into(log_routes)
+        .values(&route)
+        .on_conflict((guild_id, category))
+        .do_update()
+        .set(channel_id.eq(route.channel_id))
+        .execute(connection)?;
+    Ok(route)
+}
+
+pub fn delete_log_route(pool: &SqlitePool, guild: u64, key: &str) -> DoomResult<()> {
+    use crate::persistence::schema::log_routes::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::delete(log_routes.find((guild as i64, key))).execute(connection)?;
+    Ok(())
+}
+
+pub fn get_ignore_rules(pool: &SqlitePool) -> DoomResult<Vec<IgnoreRule>> {
+    use crate::persistence::schema::ignore_rules::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(ignore_rules
+        .select(IgnoreRule::as_select())
+        .load(connection)?)
+}
+
+/// Returns whether the rule is new.
+pub fn create_ignore_rule(pool: &SqlitePool, rule: IgnoreRule) -> DoomResult<bool> {
+    use crate::persistence::schema::ignore_rules::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let inserted = diesel::insert_or_ignore_into(ignore_rules)
+        .values(&rule)
+        .execute(connection)?;
+    Ok(inserted > 0)
+}
+
+/// Returns whether the rule existed.
+pub fn delete_ignore_rule(pool: &SqlitePool, rule: IgnoreRule) -> DoomResult<bool> {
+    use crate::persistence::schema::ignore_rules::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let deleted = diesel::delete(ignore_rules.find((rule.guild_id, rule.kind, rule.target_id)))
+        .execute(connection)?;
+    Ok(deleted > 0)
+}
+
+/// The stints of a user in a guild, oldest first.
+pub fn get_member_history(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+) -> DoomResult<Vec<MemberStint>> {
+    use crate::persistence::schema::member_history::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(member_history
+        .filter(guild_id.eq(guild as i64))
+        .filter(user_id.eq(user as i64))
+        .order(id.asc())
+        .select(MemberStint::as_select())
+        .load(connection)?)
+}
+
+pub fn record_member_join(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+    at: NaiveDateTime,
+) -> DoomResult<()> {
+    use crate::persistence::schema::member_history::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::insert_into(member_history)
+        .values(NewMemberStint {
+            guild_id: guild as i64,
+            user_id: user as i64,
+            joined_at: Some(at),
+            left_at: None,
+        })
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Closes the open stint of the user, or records one if they joined before they were tracked.
+pub fn record_member_leave(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+    joined: Option<NaiveDateTime>,
+    at: NaiveDateTime,
+) -> DoomResult<()> {
+    use crate::persistence::schema::member_history::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    connection.transaction(|connection| {
+        let open: Option<i32> = member_history
+            .filter(guild_id.eq(guild as i64))
+            .filter(user_id.eq(user as i64))
+            .filter(left_at.is_null())
+            .order(id.desc())
+            .select(id)
+            .first(connection)
+            .optional()?;
+        match open {
+            Some(open) => {
+                diesel::update(member_history.find(open))
+                    .set(left_at.eq(Some(at)))
+                    .execute(connection)?;
+            }
+            None => {
+                diesel::insert_into(member_history)
+                    .values(NewMemberStint {
+                        guild_id: guild as i64,
+                        user_id: user as i64,
+                        joined_at: joined,
+                        left_at: Some(at),
+                    })
+                    .execute(connection)?;
+            }
+        }
+        QueryResult::Ok(())
+    })?;
+    Ok(())
+}
+
+pub fn get_member_snapshot(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+) -> DoomResult<Option<MemberSnapshot>> {
+    use crate::persistence::schema::member_snapshots::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(member_snapshots
+        .find((guild as i64, user as i64))
+        .select(MemberSnapshot::as_select())
+        .first(connection)
+        .optional()?)
+}
+
+pub fn save_member_snapshot(pool: &SqlitePool, snapshot: MemberSnapshot) -> DoomResult<()> {
+    use crate::persistence::schema::member_snapshots::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::insert_into(member_snapshots)
+        .values(&snapshot)
+        .on_conflict((guild_id, user_id))
+        .do_update()
+        .set(&snapshot)
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Records a moderation action, returning its id.
+pub fn record_moderation_action(pool: &SqlitePool, action: NewModerationAction) -> DoomResult<i32> {
+    use crate::persistence::schema::moderation_actions::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(connection.transaction(|connection| {
+        diesel::insert_into(moderation_actions)
+            .values(&action)
+            .execute(connection)?;
+        moderation_actions
+            .select(id)
+            .order(id.desc())
+            .first(connection)
+    })?)
+}
+
+/// Adds the moderator found in the audit log to a recorded action.
+pub fn set_moderation_actor(
+    pool: &SqlitePool,
+    action: i32,
+    moderator: u64,
+    given_reason: Option<String>,
+) -> DoomResult<()> {
+    use crate::persistence::schema::moderation_actions::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::update(moderation_actions.find(action))
+        .set((
+            moderator_id.eq(Some(moderator as i64)),
+            reason.eq(given_reason),
+        ))
+        .execute(connection)?;
+    Ok(())
+}
+
+/// The moderation history of a user in a guild, newest first.
+pub fn get_moderation_actions(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+) -> DoomResult<Vec<ModerationAction>> {
+    use crate::persistence::schema::moderation_actions::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(moderation_actions
+        .filter(guild_id.eq(guild as i64))
+        .filter(user_id.eq(user as i64))
+        .order(id.desc())
+        .select(ModerationAction::as_select())
+        .load(connection)?)
+}
+
+/// Opens a session of the user, closing any left open by a missed leave at the same time.
+pub fn start_voice_session(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+    channel: u64,
+    at: NaiveDateTime,
+) -> DoomResult<()> {
+    use crate::persistence::schema::voice_sessions::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(connection.transaction(|connection| {
+        diesel::update(
+            voice_sessions
+                .filter(guild_id.eq(guild as i64))
+                .filter(user_id.eq(user as i64))
+                .filter(left_at.is_null()),
+        )
+        .set(left_at.eq(Some(at)))
+        .execute(connection)?;
+        diesel::insert_into(voice_sessions)
+            .values(NewVoiceSession {
+                guild_id: guild as i64,
+                user_id: user as i64,
+                channel_id: channel as i64,
+                joined_at: at,
+            })
+            .execute(connection)?;
+        QueryResult::Ok(())
+    })?)
+}
+
+/// Closes the latest open session of the user, returning when it started.
+pub fn end_voice_session(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+    at: NaiveDateTime,
+) -> DoomResult<Option<NaiveDateTime>> {
+    use crate::persistence::schema::voice_sessions::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(connection.transaction(|connection| {
+        let open: Option<(i32, NaiveDateTime)> = voice_sessions
+            .filter(guild_id.eq(guild as i64))
+            .filter(user_id.eq(user as i64))
+            .filter(left_at.is_null())
+            .order(id.desc())
+            .select((id, joined_at))
+            .first(connection)
+            .optional()?;
+        if let Some((open, _)) = open {
+            diesel::update(voice_sessions.find(open))
+                .set(left_at.eq(Some(at)))
+                .execute(connection)?;
+        }
+        QueryResult::Ok(open.map(|(_, joined)| joined))
+    })?)
+}
+
+/// The voice sessions of a user in a guild, newest first.
+pub fn get_voice_sessions(
+    pool: &SqlitePool,
+    guild: u64,
+    user: u64,
+) -> DoomResult<Vec<VoiceSession>> {
+    use crate::persistence::schema::voice_sessions::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    Ok(voice_sessions
+        .filter(guild_id.eq(guild as i64))
+        .filter(user_id.eq(user as i64))
+        .order(id.desc())
+        .select(VoiceSession::as_select())
+        .load(connection)?)
+}
+
+pub fn record_invite_use(pool: &SqlitePool, invite_use: NewInviteUse) -> DoomResult<()> {
+    use crate::persistence::schema::invite_uses::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    diesel::insert_into(invite_uses)
+        .values(&invite_use)
+        .execute(connection)?;
+    Ok(())
+}
+
+/// The users whose invites were used the most in a guild, with how often.
+pub fn get_top_inviters(pool: &SqlitePool, guild: u64, limit: i64) -> DoomResult<Vec<(i64, i64)>> {
+    use crate::persistence::schema::invite_uses::dsl::*;
+    use diesel::dsl::count_star;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let inviters: Vec<(Option<i64>, i64)> = invite_uses
+        .filter(guild_id.eq(guild as i64))
+        .filter(inviter_id.is_not_null())
+        .group_by(inviter_id)
+        .select((inviter_id, count_star()))
+        .order((count_star().desc(), inviter_id.asc()))
+        .limit(limit)
+        .load(connection)?;
+    Ok(inviters
+        .into_iter()
+        .filter_map(|(inviter, uses)| inviter.map(|inviter| (inviter, uses)))
+        .collect())
+}
+
+/// The stored pins of a channel, `None` if its pins weren't seen yet.
+pub fn get_channel_pins(pool: &SqlitePool, channel: u64) -> DoomResult<Option<BTreeSet<u64>>> {
+    use crate::persistence::schema::channel_pins::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let pins = channel_pins
+        .find(channel as i64)
+        .select(ChannelPins::as_select())
+        .first(connection)
+        .optional()?;
+    Ok(pins.map(|pins| {
+        pins.message_ids
+            .split_whitespace()
+            .filter_map(|message| message.parse().ok())
+            .collect()
+    }))
+}
+
+pub fn save_channel_pins(pool: &SqlitePool, channel: u64, pins: &BTreeSet<u64>) -> DoomResult<()> {
+    use crate::persistence::schema::channel_pins::dsl::*;
+    let connection = &mut sqlite_pool_handler(pool)?;
+    let pins = ChannelPins {
+        channel_id: channel as i64,
+        message_ids: pins
+            .iter()
+            .map(|message| message.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    diesel::insert_into(channel_pins)
+        .values(&pins)
+        .on_conflict(channel_id)
+        .do_update()
+        .set(&pins)
+        .execute(connection)?;
+    Ok(())
 }
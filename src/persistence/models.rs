@@ -1,4 +1,6 @@
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
+use poise::serenity_prelude as serenity;
 
 #[derive(Queryable, Selectable, Insertable)]
 #[diesel(table_name = crate::persistence::schema::messages)]
@@ -7,4 +9,211 @@ pub struct Message {
     pub id: i64,
     pub author: i64,
     pub content: String,
+    pub guild_id: Option<i64>,
+    pub channel_id: Option<i64>,
+    pub created_at: NaiveDateTime,
+    pub edited_at: Option<NaiveDateTime>,
+    pub reply_to: Option<i64>,
+    pub flags: i64,
+    pub pinned: bool,
+}
+
+impl From<&serenity::Message> for Message {
+    fn from(message: &serenity::Message) -> Self {
+        Message {
+            id: message.id.get() as i64,
+            author: message.author.id.get() as i64,
+            content: message.content.clone(),
+            guild_id: message.guild_id.map(|id| id.get() as i64),
+            channel_id: Some(message.channel_id.get() as i64),
+            created_at: message.timestamp.naive_utc(),
+            edited_at: message.edited_timestamp.map(|t| t.naive_utc()),
+            reply_to: message
+                .message_reference
+                .as_ref()
+                .and_then(|r| r.message_id)
+                .map(|id| id.get() as i64),
+            flags: message.flags.map_or(0, |f| f.bits() as i64),
+            pinned: message.pinned,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::persistence::schema::message_revisions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct MessageRevision {
+    pub revision: i32,
+    pub content: String,
+    pub edited_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::persistence::schema::message_revisions)]
+pub struct NewMessageRevision {
+    pub message_id: i64,
+    pub revision: i32,
+    pub content: String,
+    pub edited_at: NaiveDateTime,
+}
+
+#[derive(Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::persistence::schema::attachments)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Attachment {
+    pub id: i64,
+    pub message_id: i64,
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub size: i64,
+    pub url: String,
+    /// SHA-256 of the archived file, `None` if it wasn't archived.
+    pub hash: Option<String>,
+    /// When the attachment was edited away from its message.
+    pub removed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = crate::persistence::schema::guild_config)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct GuildConfig {
+    pub guild_id: i64,
+    /// Channel the logs of the guild are sent to, `None` falls back to `LOG_CHANNEL`.
+    pub log_channel: Option<i64>,
+    /// Whether users are sent a DM when they got ghost pinged.
+    pub notify_ghost_pings: bool,
+    /// Accounts younger than this are flagged when they join.
+    pub new_account_days: i32,
+}
+
+#[derive(Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::persistence::schema::log_routes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct LogRoute {
+    pub guild_id: i64,
+    /// Key of the [`crate::config::LogCategory`] routed to the channel.
+    pub category: String,
+    pub channel_id: i64,
+}
+
+#[derive(Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::persistence::schema::ignore_rules)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct IgnoreRule {
+    pub guild_id: i64,
+    /// Key of the [`crate::config::ignore::IgnoreKind`] of the target.
+    pub kind: String,
+    pub target_id: i64,
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        GuildConfig {
+            guild_id: 0,
+            log_channel: None,
+            notify_ghost_pings: false,
+            new_account_days: 7,
+        }
+    }
+}
+
+/// One stint of a user in a guild.
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::persistence::schema::member_history)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct MemberStint {
+    /// `None` if the user joined before they were tracked.
+    pub joined_at: Option<NaiveDateTime>,
+    /// `None` while the user is still a member.
+    pub left_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::persistence::schema::member_history)]
+pub struct NewMemberStint {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub joined_at: Option<NaiveDateTime>,
+    pub left_at: Option<NaiveDateTime>,
+}
+
+/// The last known state of a member, for when the cache doesn't have it.
+#[derive(Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = crate::persistence::schema::member_snapshots)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct MemberSnapshot {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub nick: Option<String>,
+    /// Hash of the server avatar.
+    pub avatar: Option<String>,
+    /// Space-separated role ids.
+    pub roles: String,
+    pub timeout_until: Option<NaiveDateTime>,
+}
+
+/// A ban, unban or kick, kept for the moderation history of a user.
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::persistence::schema::moderation_actions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ModerationAction {
+    /// `None` if the audit log didn't reveal the moderator.
+    pub moderator_id: Option<i64>,
+    pub kind: String,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::persistence::schema::moderation_actions)]
+pub struct NewModerationAction {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub moderator_id: Option<i64>,
+    pub kind: String,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Time a user spent in a voice channel.
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::persistence::schema::voice_sessions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct VoiceSession {
+    pub channel_id: i64,
+    pub joined_at: NaiveDateTime,
+    /// `None` while the user is still connected, or if they left while the bot was offline.
+    pub left_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::persistence::schema::voice_sessions)]
+pub struct NewVoiceSession {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub channel_id: i64,
+    pub joined_at: NaiveDateTime,
+}
+
+/// A member joining through an invite.
+#[derive(Insertable)]
+#[diesel(table_name = crate::persistence::schema::invite_uses)]
+pub struct NewInviteUse {
+    pub guild_id: i64,
+    pub user_id: i64,
+    pub code: String,
+    pub inviter_id: Option<i64>,
+    pub used_at: NaiveDateTime,
+}
+
+/// The pinned messages of a channel as of the last pin change.
+#[derive(Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = crate::persistence::schema::channel_pins)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ChannelPins {
+    pub channel_id: i64,
+    /// Space-separated message ids.
+    pub message_ids: String,
 }
@@ -2,6 +2,141 @@ diesel::table! {
     messages (id) {
         id -> Int8,
         author -> Int8,
-        content -> Text
+        content -> Text,
+        guild_id -> Nullable<Int8>,
+        channel_id -> Nullable<Int8>,
+        created_at -> Timestamp,
+        edited_at -> Nullable<Timestamp>,
+        reply_to -> Nullable<Int8>,
+        flags -> Int8,
+        pinned -> Bool,
     }
 }
+
+diesel::table! {
+    message_revisions (id) {
+        id -> Integer,
+        message_id -> Int8,
+        revision -> Integer,
+        content -> Text,
+        edited_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    attachments (id) {
+        id -> Int8,
+        message_id -> Int8,
+        filename -> Text,
+        content_type -> Nullable<Text>,
+        size -> Int8,
+        url -> Text,
+        hash -> Nullable<Text>,
+        removed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    guild_config (guild_id) {
+        guild_id -> Int8,
+        log_channel -> Nullable<Int8>,
+        notify_ghost_pings -> Bool,
+        new_account_days -> Integer,
+    }
+}
+
+diesel::table! {
+    log_routes (guild_id, category) {
+        guild_id -> Int8,
+        category -> Text,
+        channel_id -> Int8,
+    }
+}
+
+diesel::table! {
+    ignore_rules (guild_id, kind, target_id) {
+        guild_id -> Int8,
+        kind -> Text,
+        target_id -> Int8,
+    }
+}
+
+diesel::table! {
+    member_history (id) {
+        id -> Integer,
+        guild_id -> Int8,
+        user_id -> Int8,
+        joined_at -> Nullable<Timestamp>,
+        left_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    member_snapshots (guild_id, user_id) {
+        guild_id -> Int8,
+        user_id -> Int8,
+        nick -> Nullable<Text>,
+        avatar -> Nullable<Text>,
+        roles -> Text,
+        timeout_until -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    moderation_actions (id) {
+        id -> Integer,
+        guild_id -> Int8,
+        user_id -> Int8,
+        moderator_id -> Nullable<Int8>,
+        kind -> Text,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    voice_sessions (id) {
+        id -> Integer,
+        guild_id -> Int8,
+        user_id -> Int8,
+        channel_id -> Int8,
+        joined_at -> Timestamp,
+        left_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    invite_uses (id) {
+        id -> Integer,
+        guild_id -> Int8,
+        user_id -> Int8,
+        code -> Text,
+        inviter_id -> Nullable<Int8>,
+        used_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    channel_pins (channel_id) {
+        channel_id -> Int8,
+        message_ids -> Text,
+    }
+}
+
+diesel::joinable!(message_revisions -> messages (message_id));
+diesel::joinable!(attachments -> messages (message_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    messages,
+    message_revisions,
+    attachments,
+    guild_config,
+    log_routes,
+    ignore_rules,
+    member_history,
+    member_snapshots,
+    moderation_actions,
+    voice_sessions,
+    invite_uses,
+    channel_pins,
+);
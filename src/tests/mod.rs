@@ -51,3 +51,1429 @@ fn test_limit_content_and_see_more() {
     assert!(full_changelog.is_ok());
     assert_eq!(full_changelog.unwrap().len(), 1144);
 }
+
+/// Returns a path in the temp directory that is unique to this test run.
+fn temp_path(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    std::env::temp_dir().join(format!(
+        "kanshi-test-{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst),
+        name
+    ))
+}
+
+/// Creates a fresh, fully migrated SQLite database in the temp directory.
+fn test_pool() -> crate::persistence::SqlitePool {
+    let path = temp_path("db.sqlite");
+    let _ = std::fs::remove_file(&path);
+    let pool = crate::persistence::init_pool(path.to_str().unwrap()).unwrap();
+    crate::run_migrations(pool.get().unwrap()).unwrap();
+    pool
+}
+
+#[test]
+fn test_message_revisions() {
+    use crate::persistence::models::Message;
+    use crate::persistence::{
+        add_message_revision, create_messages, get_message_by_id, get_message_revisions,
+    };
+    use chrono::DateTime;
+
+    let pool = test_pool();
+    let at = |secs| DateTime::from_timestamp(secs, 0).unwrap().naive_utc();
+
+    create_messages(
+        &pool,
+        &[&Message {
+            id: 1,
+            author: 2,
+            content: "first".to_string(),
+            guild_id: Some(3),
+            channel_id: Some(4),
+            created_at: at(100),
+            edited_at: None,
+            reply_to: None,
+            flags: 0,
+            pinned: false,
+        }],
+    )
+    .unwrap();
+    assert_eq!(
+        add_message_revision(&pool, 1, "second".to_string(), at(200)).unwrap(),
+        2
+    );
+    assert_eq!(
+        add_message_revision(&pool, 1, "third".to_string(), at(300)).unwrap(),
+        3
+    );
+
+    let message = get_message_by_id(&pool, 1).unwrap().unwrap();
+    assert_eq!(message.content, "third");
+    assert_eq!(message.edited_at, Some(at(300)));
+    assert_eq!(message.created_at, at(100));
+
+    let revisions = get_message_revisions(&pool, 1).unwrap();
+    assert_eq!(revisions.len(), 3);
+    let contents: Vec<_> = revisions.iter().map(|r| r.content.as_str()).collect();
+    assert_eq!(contents, vec!["first", "second", "third"]);
+    assert_eq!(revisions[1].edited_at, at(200));
+}
+
+/// Serves `body` to a single request, standing in for the Discord CDN.
+async fn serve_once(content_type: &'static str, body: Vec<u8>) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = [0; 4096];
+        let _ = stream.read(&mut request).await.unwrap();
+        let header = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            content_type,
+            body.len()
+        );
+        stream.write_all(header.as_bytes()).await.unwrap();
+        stream.write_all(&body).await.unwrap();
+    });
+    format!("http://{}/attachments/1/2/file", address)
+}
+
+#[tokio::test]
+async fn test_attachment_archive() {
+    use crate::archive::{ArchiveConfig, AttachmentArchive};
+
+    let archive = AttachmentArchive::new(ArchiveConfig {
+        directory: temp_path("attachments"),
+        max_size: 16,
+        content_types: vec!["image/*".to_string(), "text/plain".to_string()],
+    });
+
+    let body = b"not really a png".to_vec();
+    let url = serve_once("image/png", body.clone()).await;
+    let hash = archive
+        .archive(&url, Some("image/png"), body.len() as u64)
+        .await
+        .unwrap()
+        .expect("Image should be archived");
+    assert_eq!(
+        hash,
+        "e90137d39de304eefbbe788bc535c7e82f27abbf8069505fbbd8a9dcdc4f2024"
+    );
+    assert!(archive
+        .path_of(&hash)
+        .ends_with(format!("{}/{}", &hash[..2], hash)));
+    assert_eq!(archive.load(&hash).await.unwrap(), body);
+
+    // Identical content ends up at the same address.
+    let url = serve_once("image/png", body.clone()).await;
+    let again = archive.archive(&url, Some("image/png"), 16).await.unwrap();
+    assert_eq!(again, Some(hash));
+
+    // Disallowed types and announced oversized files are skipped without any request.
+    let skipped = archive
+        .archive("http://127.0.0.1:1/", Some("application/zip"), 1)
+        .await;
+    assert!(skipped.unwrap().is_none());
+    let skipped = archive
+        .archive("http://127.0.0.1:1/", Some("text/plain; charset=utf-8"), 17)
+        .await;
+    assert!(skipped.unwrap().is_none());
+
+    // The cap also holds when the announced size was a lie.
+    let url = serve_once("text/plain", vec![b'a'; 64]).await;
+    let skipped = archive.archive(&url, Some("text/plain"), 1).await;
+    assert!(skipped.unwrap().is_none());
+}
+
+#[test]
+fn test_detect_message_changes() {
+    use crate::event::changes::{detect_changes, MessageChange, MessageState};
+    use poise::serenity_prelude::MessageFlags;
+
+    let before = MessageState {
+        attachments: Some(vec![1, 2]),
+        flags: Some(MessageFlags::empty()),
+        pinned: Some(false),
+        components: Some(serde_json::json!([])),
+    };
+
+    // An update which doesn't carry a part can't change it.
+    assert!(detect_changes(&before, &MessageState::default()).is_empty());
+
+    let after = MessageState {
+        attachments: Some(vec![2]),
+        flags: Some(MessageFlags::SUPPRESS_EMBEDS | MessageFlags::SUPPRESS_NOTIFICATIONS),
+        pinned: Some(true),
+        components: Some(serde_json::json!([{ "type": 1, "components": [] }])),
+    };
+    assert_eq!(
+        detect_changes(&before, &after),
+        vec![
+            MessageChange::AttachmentsRemoved(vec![1]),
+            MessageChange::EmbedsSuppressed,
+            MessageChange::FlagsChanged {
+                added: MessageFlags::SUPPRESS_NOTIFICATIONS,
+                removed: MessageFlags::empty(),
+            },
+            MessageChange::Pinned,
+            MessageChange::ComponentsChanged,
+        ]
+    );
+
+    assert_eq!(
+        detect_changes(&after, &before),
+        vec![
+            MessageChange::EmbedsRestored,
+            MessageChange::FlagsChanged {
+                added: MessageFlags::empty(),
+                removed: MessageFlags::SUPPRESS_NOTIFICATIONS,
+            },
+            MessageChange::Unpinned,
+            MessageChange::ComponentsChanged,
+        ]
+    );
+}
+
+#[test]
+fn test_transcripts() {
+    use crate::persistence::models::Message;
+    use crate::transcript;
+    use chrono::DateTime;
+    use std::collections::HashMap;
+
+    let message = |id, author, content: &str, edited: bool| Message {
+        id,
+        author,
+        content: content.to_string(),
+        guild_id: Some(1),
+        channel_id: Some(2),
+        created_at: DateTime::from_timestamp(1_700_000_000 + id, 0)
+            .unwrap()
+            .naive_utc(),
+        edited_at: edited.then(|| DateTime::from_timestamp(0, 0).unwrap().naive_utc()),
+        reply_to: None,
+        flags: 0,
+        pinned: false,
+    };
+    let messages = vec![
+        message(1, 10, "hello", false),
+        message(2, 20, "<script>alert('hi')</script>", true),
+    ];
+    let names = HashMap::from([(10, "alice".to_string())]);
+
+    assert_eq!(
+        transcript::plain_text("Purge", &messages, &names),
+        "Purge\n=====\n\
+         \n[2023-11-14 22:13:21] alice (10)\nhello\n\
+         \n[2023-11-14 22:13:22] <unknown user> (20) (edited)\n<script>alert('hi')</script>\n"
+    );
+
+    let html = transcript::html("Purge", &messages, &names);
+    assert!(html.contains("<span class=\"author\">alice</span>"));
+    assert!(html.contains("&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt;"));
+    assert!(!html.contains("<script>"));
+}
+
+/// Fills a fresh database with a burst of thousands of concurrently created messages,
+/// reporting the throughput of the batching writer.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_database_burst_throughput() {
+    use crate::error::DoomError;
+    use crate::persistence::database::Database;
+    use crate::persistence::get_message_count;
+    use crate::persistence::models::Message;
+    use chrono::Utc;
+    use std::time::{Duration, Instant};
+    use tokio::task::JoinSet;
+
+    const BURST: i64 = 5000;
+
+    let db = Database::new(test_pool());
+    let started = Instant::now();
+    let mut tasks = JoinSet::new();
+    for id in 1..=BURST {
+        let db = db.clone();
+        tasks.spawn(async move {
+            db.create_message(Message {
+                id,
+                author: id % 50,
+                content: format!("Message #{}", id),
+                guild_id: Some(1),
+                channel_id: Some(2),
+                created_at: Utc::now().naive_utc(),
+                edited_at: None,
+                reply_to: None,
+                flags: 0,
+                pinned: false,
+            })
+            .await
+        });
+    }
+    while let Some(result) = tasks.join_next().await {
+        result.unwrap().unwrap();
+    }
+    let elapsed = started.elapsed();
+
+    assert_eq!(db.run(get_message_count).await.unwrap(), BURST);
+    // Batched writes keep up with a burst easily, even in unoptimized test builds.
+    assert!(
+        elapsed < Duration::from_secs(30),
+        "Storing {} messages took {:?}",
+        BURST,
+        elapsed
+    );
+
+    // A duplicate fails on its own, without taking the rest of its batch down.
+    let duplicate = |id| Message {
+        id,
+        author: 1,
+        content: String::new(),
+        guild_id: None,
+        channel_id: None,
+        created_at: Utc::now().naive_utc(),
+        edited_at: None,
+        reply_to: None,
+        flags: 0,
+        pinned: false,
+    };
+    let (first, second) = tokio::join!(
+        db.create_message(duplicate(1)),
+        db.create_message(duplicate(BURST + 1))
+    );
+    assert!(matches!(first, Err(DoomError::DatabaseError(_))));
+    assert!(second.is_ok());
+}
+
+#[test]
+fn test_environment_variables() {
+    use crate::error::DoomError;
+    use crate::util::env::{required_var, var_or};
+
+    std::env::set_var("KANSHI_TEST_NUMBER", " 42 ");
+    std::env::set_var("KANSHI_TEST_GARBAGE", "forty-two");
+
+    assert_eq!(required_var::<u64>("KANSHI_TEST_NUMBER").unwrap(), 42);
+    assert_eq!(var_or("KANSHI_TEST_UNSET", 7u64).unwrap(), 7);
+    assert!(matches!(
+        var_or("KANSHI_TEST_GARBAGE", 7u64),
+        Err(DoomError::ConfigError { variable, .. }) if variable == "KANSHI_TEST_GARBAGE"
+    ));
+    assert!(required_var::<u64>("KANSHI_TEST_UNSET").is_err());
+}
+
+#[test]
+fn test_guild_config() {
+    use crate::config::{ConfigCache, LogCategory};
+    use crate::persistence::models::{GuildConfig, LogRoute};
+    use crate::persistence::{delete_log_route, save_guild_config, save_log_route};
+    use poise::serenity_prelude::{ChannelId, GuildId};
+
+    let pool = test_pool();
+    let default = ChannelId::new(1);
+    let (first, second) = (GuildId::new(10), GuildId::new(20));
+    let edits = LogCategory::MessageEdits;
+
+    save_guild_config(
+        &pool,
+        GuildConfig {
+            guild_id: 10,
+            log_channel: Some(11),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let route = |channel_id| LogRoute {
+        guild_id: 20,
+        category: edits.key().to_string(),
+        channel_id,
+    };
+    save_log_route(&pool, route(21)).unwrap();
+    save_log_route(&pool, route(22)).unwrap();
+
+    let cache = ConfigCache::load(&pool, Some(default)).unwrap();
+    assert_eq!(
+        cache.log_channel(Some(first), edits),
+        Some(ChannelId::new(11))
+    );
+    assert_eq!(
+        cache.log_channel(Some(second), edits),
+        Some(ChannelId::new(22))
+    );
+    assert_eq!(
+        cache.log_channel(Some(second), LogCategory::MessageDeletes),
+        Some(default)
+    );
+    assert_eq!(cache.log_channel(None, edits), Some(default));
+    assert_eq!(
+        cache.log_channels(LogCategory::BotStatus),
+        vec![default, ChannelId::new(11)]
+    );
+
+    // Resetting the channel has to overwrite the stored one.
+    let mut config = cache.get(first);
+    config.log_channel = None;
+    cache.update(save_guild_config(&pool, config).unwrap());
+    assert_eq!(cache.log_channel(Some(first), edits), Some(default));
+    delete_log_route(&pool, 20, edits.key()).unwrap();
+
+    let reloaded = ConfigCache::load(&pool, None).unwrap();
+    assert_eq!(reloaded.log_channel(Some(first), edits), None);
+    assert_eq!(reloaded.log_channel(Some(second), edits), None);
+    assert!(reloaded.log_channels(edits).is_empty());
+    assert_eq!(
+        LogCategory::from_key("bot_status"),
+        Some(LogCategory::BotStatus)
+    );
+}
+
+#[test]
+fn test_ignore_rules() {
+    use crate::config::ignore::{EventScope, IgnoreKind};
+    use crate::config::ConfigCache;
+    use crate::persistence::models::IgnoreRule;
+    use crate::persistence::{create_ignore_rule, delete_ignore_rule};
+    use poise::serenity_prelude::{ChannelId, GuildId, RoleId, UserId};
+
+    let pool = test_pool();
+    let guild = GuildId::new(10);
+    let rule = |kind: IgnoreKind, target_id| IgnoreRule {
+        guild_id: 10,
+        kind: kind.key().to_string(),
+        target_id,
+    };
+    assert!(create_ignore_rule(&pool, rule(IgnoreKind::Category, 100)).unwrap());
+    assert!(create_ignore_rule(&pool, rule(IgnoreKind::Role, 200)).unwrap());
+    assert!(create_ignore_rule(&pool, rule(IgnoreKind::User, 300)).unwrap());
+    assert!(!create_ignore_rule(&pool, rule(IgnoreKind::User, 300)).unwrap());
+
+    let cache = ConfigCache::load(&pool, None).unwrap();
+    let scope = |channels: &[u64], user: Option<u64>, roles: &[u64]| {
+        let channels: Vec<ChannelId> = channels.iter().map(|id| ChannelId::new(*id)).collect();
+        let roles: Vec<RoleId> = roles.iter().map(|id| RoleId::new(*id)).collect();
+        cache.is_ignored(
+            guild,
+            &EventScope {
+                channels: &channels,
+                user: user.map(UserId::new),
+                roles: &roles,
+            },
+        )
+    };
+
+    assert!(!scope(&[1, 2], Some(3), &[4]));
+    // Threads of channels within an ignored category are ignored as well.
+    assert!(scope(&[1, 2, 100], Some(3), &[4]));
+    assert!(scope(&[1], Some(3), &[4, 200]));
+    assert!(scope(&[1], Some(300), &[]));
+    assert!(!cache.is_ignored(
+        GuildId::new(20),
+        &EventScope {
+            channels: &[ChannelId::new(100)],
+            user: Some(UserId::new(300)),
+            roles: &[],
+        },
+    ));
+
+    assert!(delete_ignore_rule(&pool, rule(IgnoreKind::User, 300)).unwrap());
+    assert!(!delete_ignore_rule(&pool, rule(IgnoreKind::User, 300)).unwrap());
+    cache.set_ignored(guild, IgnoreKind::User, 300, false);
+    assert!(!scope(&[1], Some(300), &[]));
+    cache.set_ignored(guild, IgnoreKind::Channel, 1, true);
+    assert!(scope(&[1], None, &[]));
+
+    let reloaded = ConfigCache::load(&pool, None).unwrap();
+    assert_eq!(
+        reloaded.ignore_rules(guild).iter().collect::<Vec<_>>(),
+        vec![(IgnoreKind::Category, 100), (IgnoreKind::Role, 200)]
+    );
+}
+
+#[test]
+fn test_diff() {
+    use crate::diff::{diff, render, Change};
+
+    let join = |changes: &[Change], removed: bool| -> String {
+        changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::Equal(text) => Some(text.as_str()),
+                Change::Removed(text) if removed => Some(text.as_str()),
+                Change::Added(text) if !removed => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    };
+    let pairs = [
+        ("the quick brown fox", "the quick red fox"),
+        ("teh cat", "the cat"),
+        ("", "new"),
+        ("gone", ""),
+        ("line one\nline two", "line one\n\nline two!"),
+        ("日本語のテキスト です", "日本語のテキスト でした"),
+    ];
+    for (old, new) in pairs {
+        let changes = diff(old, new);
+        assert_eq!(join(&changes, true), old);
+        assert_eq!(join(&changes, false), new);
+    }
+
+    assert_eq!(
+        diff("the quick brown fox", "the quick red fox"),
+        vec![
+            Change::Equal("the quick ".to_string()),
+            Change::Removed("brown".to_string()),
+            Change::Added("red".to_string()),
+            Change::Equal(" fox".to_string()),
+        ]
+    );
+    // Typo fixes are diffed by character.
+    assert_eq!(
+        render(&diff("recieve it", "receive it"), 1024),
+        "rec~~i~~e**i**ve it"
+    );
+    assert_eq!(render(&diff("a b", "a  b"), 1024), "a **·**b");
+    assert_eq!(
+        render(&diff("x *y*", "x _y_"), 1024),
+        "x ~~\\*y\\*~~**\\_y\\_**"
+    );
+
+    // Unchanged text is shortened around the change to fit the limit.
+    let padding = "lorem ipsum ".repeat(200);
+    let old = format!("{}typo{}", padding, padding);
+    let new = format!("{}type{}", padding, padding);
+    let rendered = render(&diff(&old, &new), 1024);
+    assert!(rendered.chars().count() <= 1024);
+    assert!(rendered.starts_with('…') && rendered.ends_with('…'));
+    assert!(rendered.contains("typ~~o~~**e**"));
+
+    // Cut between changes, so no marker is left open.
+    let balanced = |rendered: &str| {
+        rendered.matches("~~").count().is_multiple_of(2)
+            && rendered.matches("**").count().is_multiple_of(2)
+    };
+    let rendered = render(&diff(&padding, &padding.to_uppercase()), 100);
+    assert!(rendered.chars().count() <= 100 && rendered.ends_with('…'));
+    assert!(balanced(&rendered));
+    let rendered = render(&diff("", &"*word* ".repeat(50)), 100);
+    assert!(rendered.chars().count() <= 100 && rendered.starts_with("**\\*word"));
+    assert!(balanced(&rendered.replace("\\*", "")));
+}
+
+#[test]
+fn test_truncate() {
+    use crate::util::discord::{truncate, truncate_escaped, FIELD_LIMIT};
+
+    assert_eq!(truncate("short", 10), "short");
+    assert_eq!(truncate("exactly10!", 10), "exactly10!");
+    assert_eq!(truncate("a bit too long", 10), "a bit too…");
+
+    // Byte 1024 falls within a character here, which String::truncate panics on.
+    let japanese = "監視".repeat(600);
+    let truncated = truncate(&japanese, FIELD_LIMIT);
+    assert_eq!(truncated.chars().count(), FIELD_LIMIT);
+    assert!(truncated.ends_with("監…"));
+
+    // Combining marks and emoji sequences stay in one piece.
+    let combined = "e\u{301}".repeat(10);
+    assert_eq!(truncate(&combined, 6), "e\u{301}e\u{301}…");
+    let family = "👩‍👩‍👧".repeat(3);
+    assert_eq!(truncate(&family, 8), "👩‍👩‍👧…");
+
+    // Escapes are never cut off from the character they escape.
+    assert_eq!(truncate_escaped("dev_ops", 10), "dev\\_ops");
+    assert_eq!(truncate_escaped("a_b_c_d_e", 10), "a\\_b\\_c\\_…");
+    assert!(truncate_escaped(&"_".repeat(100), 11).chars().count() <= 11);
+}
+
+/// Stands in for Discord's audit log, serving whatever entries the test puts in.
+#[derive(Default)]
+struct AuditStandIn {
+    entries: std::sync::Mutex<Vec<crate::audit::AuditEntry>>,
+}
+
+impl crate::audit::AuditSource for AuditStandIn {
+    async fn entries(
+        &self,
+        _guild_id: poise::serenity_prelude::GuildId,
+        _action: poise::serenity_prelude::audit_log::Action,
+        limit: u8,
+    ) -> crate::error::DoomResult<Vec<crate::audit::AuditEntry>> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.iter().take(limit as usize).cloned().collect())
+    }
+}
+
+#[tokio::test]
+async fn test_deletion_attribution() {
+    use crate::audit::{deleted_by_author, AuditEntry, DeletionTracker};
+    use chrono::{DateTime, TimeDelta};
+    use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+
+    let now = DateTime::from_timestamp(1_700_000_000, 0)
+        .unwrap()
+        .naive_utc();
+    let (guild, channel, other_channel) = (GuildId::new(1), ChannelId::new(2), ChannelId::new(3));
+    let (author, moderator, other_moderator) = (UserId::new(4), UserId::new(5), UserId::new(6));
+    let entry = |id, user_id, count, age| AuditEntry {
+        id,
+        user_id,
+        target_id: Some(author.get()),
+        reason: None,
+        channel_id: Some(channel),
+        count,
+        created_at: now - TimeDelta::seconds(age),
+    };
+
+    let source = AuditStandIn::default();
+    let tracker = DeletionTracker::default();
+    let find = |channel| tracker.find_deleter(&source, guild, channel, author, now);
+
+    // An old entry only serves as the baseline, the author deleted the message themselves.
+    *source.entries.lock().unwrap() = vec![entry(10, other_moderator, 3, 3600)];
+    assert!(find(channel).await.unwrap().is_none());
+
+    *source.entries.lock().unwrap() = vec![
+        entry(20, moderator, 1, 2),
+        entry(10, other_moderator, 3, 3600),
+    ];
+    assert!(find(other_channel).await.unwrap().is_none());
+    let deleter = find(channel).await.unwrap().unwrap();
+    assert_eq!(deleter.user_id, moderator);
+    assert!(find(channel).await.unwrap().is_none());
+
+    // Further deletions are added to the existing entries, one event per deletion.
+    *source.entries.lock().unwrap() = vec![
+        entry(20, moderator, 3, 2),
+        entry(10, other_moderator, 4, 3600),
+    ];
+    for expected in [moderator, moderator, other_moderator] {
+        assert_eq!(find(channel).await.unwrap().unwrap().user_id, expected);
+    }
+    assert!(find(channel).await.unwrap().is_none());
+
+    // Only messages the author removed themselves count as ghost pings.
+    assert!(deleted_by_author(author, None));
+    assert!(deleted_by_author(author, Some(&entry(30, author, 1, 0))));
+    assert!(!deleted_by_author(
+        author,
+        Some(&entry(30, moderator, 1, 0))
+    ));
+}
+
+#[test]
+fn test_mentions() {
+    use crate::event::mentions::Mentions;
+    use poise::serenity_prelude::{RoleId, UserId};
+
+    let before = Mentions::parse("hey <@1> <@!2> and <@&3>, @everyone look `<@4>` ```\n<@5>\n```");
+    assert_eq!(
+        before.users.iter().copied().collect::<Vec<_>>(),
+        vec![UserId::new(1), UserId::new(2)]
+    );
+    assert_eq!(
+        before.roles.iter().copied().collect::<Vec<_>>(),
+        vec![RoleId::new(3)]
+    );
+    assert!(before.everyone && !before.here);
+
+    let after = Mentions::parse("hey <@1>");
+    let removed = before.removed(&after);
+    assert_eq!(
+        removed.users.iter().copied().collect::<Vec<_>>(),
+        vec![UserId::new(2)]
+    );
+    assert!(removed.everyone);
+    assert!(!removed.is_empty());
+    assert!(before.removed(&before).is_empty());
+    assert!(removed.intersection(&after).is_empty());
+    assert!(!removed.describe().contains("@everyone"));
+
+    assert!(Mentions::parse("mail me @ home, <@0> isn't a user").is_empty());
+}
+
+#[test]
+fn test_member_history() {
+    use crate::persistence::{get_member_history, record_member_join, record_member_leave};
+    use chrono::DateTime;
+
+    let pool = test_pool();
+    let at = |secs| DateTime::from_timestamp(secs, 0).unwrap().naive_utc();
+
+    // A member who joined before they were tracked gets a stint on leaving.
+    record_member_leave(&pool, 1, 2, Some(at(50)), at(100)).unwrap();
+    record_member_join(&pool, 1, 2, at(200)).unwrap();
+    record_member_join(&pool, 9, 2, at(250)).unwrap();
+    record_member_leave(&pool, 1, 2, None, at(300)).unwrap();
+
+    let history = get_member_history(&pool, 1, 2).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].joined_at, Some(at(50)));
+    assert_eq!(history[0].left_at, Some(at(100)));
+    assert_eq!(history[1].joined_at, Some(at(200)));
+    assert_eq!(history[1].left_at, Some(at(300)));
+    assert_eq!(get_member_history(&pool, 9, 2).unwrap().len(), 1);
+    assert!(get_member_history(&pool, 1, 3).unwrap().is_empty());
+}
+
+#[test]
+fn test_join_limited() {
+    use crate::util::discord::join_limited;
+
+    let items: Vec<String> = (0..100).map(|i| format!("<@&{:018}>", i)).collect();
+    assert_eq!(
+        join_limited(&items[..2], " ", 1024),
+        format!("{} {}", items[0], items[1])
+    );
+    let joined = join_limited(&items, " ", 1024);
+    assert!(joined.chars().count() <= 1024);
+    assert!(joined.ends_with("more"));
+    assert!(joined.starts_with(&items[0]));
+    assert_eq!(join_limited(&[], " ", 1024), "");
+}
+
+#[test]
+fn test_member_changes() {
+    use crate::event::member::{detect_member_changes, MemberChange, MemberState};
+    use crate::persistence::{get_member_snapshot, save_member_snapshot};
+    use chrono::DateTime;
+    use poise::serenity_prelude::{GuildId, RoleId, UserId};
+
+    let at = |secs| DateTime::from_timestamp(secs, 0).unwrap().naive_utc();
+    let roles = |ids: &[u64]| ids.iter().map(|id| RoleId::new(*id)).collect();
+    let before = MemberState {
+        nick: Some("old".to_string()),
+        avatar: None,
+        roles: roles(&[1, 2]),
+        timeout_until: Some(at(50)),
+    };
+    assert!(detect_member_changes(&before, &before.clone(), at(100)).is_empty());
+
+    // A timeout which ran out already isn't one anymore.
+    let after = MemberState {
+        nick: None,
+        avatar: Some("a_hash".to_string()),
+        roles: roles(&[2, 3]),
+        timeout_until: Some(at(200)),
+    };
+    assert_eq!(
+        detect_member_changes(&before, &after, at(100)),
+        vec![
+            MemberChange::RolesAdded(vec![RoleId::new(3)]),
+            MemberChange::RolesRemoved(vec![RoleId::new(1)]),
+            MemberChange::Nickname {
+                before: Some("old".to_string()),
+                after: None,
+            },
+            MemberChange::Avatar {
+                before: None,
+                after: Some("a_hash".to_string()),
+            },
+            MemberChange::TimedOut(at(200)),
+        ]
+    );
+    let cleared = MemberState {
+        timeout_until: None,
+        ..after.clone()
+    };
+    assert_eq!(
+        detect_member_changes(&after, &cleared, at(100)),
+        vec![MemberChange::TimeoutRemoved]
+    );
+    assert!(detect_member_changes(&after, &cleared, at(300)).is_empty());
+
+    // Snapshots stand in for the cache and are overwritten with each update.
+    let pool = test_pool();
+    let (guild, user) = (GuildId::new(1), UserId::new(2));
+    assert!(get_member_snapshot(&pool, 1, 2).unwrap().is_none());
+    save_member_snapshot(&pool, before.to_snapshot(guild, user)).unwrap();
+    save_member_snapshot(&pool, after.to_snapshot(guild, user)).unwrap();
+    let snapshot = get_member_snapshot(&pool, 1, 2).unwrap().unwrap();
+    assert_eq!(MemberState::from_snapshot(snapshot), after);
+}
+
+#[test]
+fn test_audit_correlation() {
+    use crate::audit::{correlate_target, AuditEntry};
+    use chrono::{DateTime, TimeDelta};
+    use poise::serenity_prelude::UserId;
+
+    let now = DateTime::from_timestamp(1_700_000_000, 0)
+        .unwrap()
+        .naive_utc();
+    let entry = |id, target, age| AuditEntry {
+        id,
+        user_id: UserId::new(7),
+        target_id: Some(target),
+        reason: None,
+        channel_id: None,
+        count: 1,
+        created_at: now - TimeDelta::seconds(age),
+    };
+    let entries = vec![entry(3, 1, 2), entry(2, 2, 60), entry(1, 2, 1)];
+    assert_eq!(correlate_target(&entries, 1, now).unwrap().id, 3);
+    // Only the latest entry about the target counts, which is too old here.
+    assert!(correlate_target(&entries, 2, now).is_none());
+    assert!(correlate_target(&entries, 4, now).is_none());
+}
+
+#[test]
+fn test_moderation_actions() {
+    use crate::event::moderation::ModerationKind;
+    use crate::persistence::models::NewModerationAction;
+    use crate::persistence::{
+        get_moderation_actions, record_moderation_action, set_moderation_actor,
+    };
+    use chrono::DateTime;
+
+    for kind in ModerationKind::ALL {
+        assert_eq!(ModerationKind::from_key(kind.key()), Some(kind));
+    }
+    assert_eq!(ModerationKind::from_key("warn"), None);
+
+    let pool = test_pool();
+    let action = |guild, kind: ModerationKind, moderator, secs| NewModerationAction {
+        guild_id: guild,
+        user_id: 2,
+        moderator_id: moderator,
+        kind: kind.key().to_string(),
+        reason: moderator.map(|_| "spam".to_string()),
+        created_at: DateTime::from_timestamp(secs, 0).unwrap().naive_utc(),
+    };
+    record_moderation_action(&pool, action(1, ModerationKind::Kick, None, 100)).unwrap();
+    record_moderation_action(&pool, action(1, ModerationKind::Ban, Some(3), 200)).unwrap();
+    record_moderation_action(&pool, action(9, ModerationKind::Ban, Some(3), 300)).unwrap();
+    // The moderator is filled in once the audit log has them.
+    let unban = record_moderation_action(&pool, action(1, ModerationKind::Unban, None, 400));
+    set_moderation_actor(&pool, unban.unwrap(), 4, None).unwrap();
+
+    let history = get_moderation_actions(&pool, 1, 2).unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].kind, "unban");
+    assert_eq!(history[0].moderator_id, Some(4));
+    let history = &history[1..];
+    assert_eq!(history[0].kind, "ban");
+    assert_eq!(history[0].moderator_id, Some(3));
+    assert_eq!(history[0].reason.as_deref(), Some("spam"));
+    assert_eq!(history[1].kind, "kick");
+    assert_eq!(history[1].moderator_id, None);
+    assert!(get_moderation_actions(&pool, 1, 3).unwrap().is_empty());
+}
+
+#[test]
+fn test_channel_changes() {
+    use crate::event::channel::{
+        detect_channel_changes, ChannelChange, ChannelState, OverwriteTarget, PermissionState,
+    };
+    use crate::util::discord::permission_name;
+    use poise::serenity_prelude::{ChannelId, Permissions, RoleId, UserId};
+
+    let (role, member) = (
+        OverwriteTarget::Role(RoleId::new(1)),
+        OverwriteTarget::Member(UserId::new(2)),
+    );
+    let before = ChannelState {
+        name: "general".to_string(),
+        topic: None,
+        slowmode: 0,
+        nsfw: false,
+        parent_id: Some(ChannelId::new(3)),
+        overwrites: [
+            (
+                role,
+                (Permissions::SEND_MESSAGES, Permissions::ATTACH_FILES),
+            ),
+            (member, (Permissions::empty(), Permissions::empty())),
+        ]
+        .into(),
+    };
+    assert!(detect_channel_changes(&before, &before.clone()).is_empty());
+
+    let after = ChannelState {
+        name: "chat".to_string(),
+        topic: Some("Be nice".to_string()),
+        slowmode: 30,
+        nsfw: true,
+        parent_id: None,
+        overwrites: [(
+            role,
+            (
+                Permissions::ATTACH_FILES,
+                Permissions::SEND_MESSAGES | Permissions::EMBED_LINKS,
+            ),
+        )]
+        .into(),
+    };
+    assert_eq!(
+        detect_channel_changes(&before, &after),
+        vec![
+            ChannelChange::Name {
+                before: "general".to_string(),
+                after: "chat".to_string(),
+            },
+            ChannelChange::Topic {
+                before: None,
+                after: Some("Be nice".to_string()),
+            },
+            ChannelChange::Slowmode {
+                before: 0,
+                after: 30,
+            },
+            ChannelChange::Nsfw(true),
+            ChannelChange::Parent {
+                before: Some(ChannelId::new(3)),
+                after: None,
+            },
+            ChannelChange::Overwrite {
+                target: role,
+                added: false,
+                removed: false,
+                permissions: vec![
+                    (
+                        Permissions::SEND_MESSAGES,
+                        PermissionState::Allow,
+                        PermissionState::Deny,
+                    ),
+                    (
+                        Permissions::EMBED_LINKS,
+                        PermissionState::Inherit,
+                        PermissionState::Deny,
+                    ),
+                    (
+                        Permissions::ATTACH_FILES,
+                        PermissionState::Deny,
+                        PermissionState::Allow,
+                    ),
+                ],
+            },
+            // An empty overwrite still counts when it's removed.
+            ChannelChange::Overwrite {
+                target: member,
+                added: false,
+                removed: true,
+                permissions: vec![],
+            },
+        ]
+    );
+
+    assert_eq!(permission_name(Permissions::ADMINISTRATOR), "Administrator");
+    assert_eq!(permission_name(Permissions::empty()), "Unknown Permission");
+}
+
+#[test]
+fn test_role_changes() {
+    use crate::event::role::{describe_permissions, detect_role_changes, RoleChange, RoleState};
+    use poise::serenity_prelude::Permissions;
+
+    let before = RoleState {
+        name: "Helper".to_string(),
+        colour: 0,
+        hoist: false,
+        mentionable: true,
+        position: 3,
+        permissions: Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES,
+    };
+    assert!(detect_role_changes(&before, &before.clone()).is_empty());
+
+    let after = RoleState {
+        name: "Moderator".to_string(),
+        colour: 0x3498db,
+        hoist: true,
+        mentionable: false,
+        position: 5,
+        permissions: Permissions::SEND_MESSAGES | Permissions::MANAGE_WEBHOOKS,
+    };
+    assert_eq!(
+        detect_role_changes(&before, &after),
+        vec![
+            RoleChange::Name {
+                before: "Helper".to_string(),
+                after: "Moderator".to_string(),
+            },
+            RoleChange::Colour {
+                before: 0,
+                after: 0x3498db,
+            },
+            RoleChange::Hoist(true),
+            RoleChange::Mentionable(false),
+            RoleChange::Position {
+                before: 3,
+                after: 5,
+            },
+            RoleChange::Permissions {
+                gained: Permissions::MANAGE_WEBHOOKS,
+                lost: Permissions::ATTACH_FILES,
+            },
+        ]
+    );
+
+    // Reordering roles alone isn't worth a log.
+    let moved = RoleState {
+        position: 4,
+        ..before.clone()
+    };
+    assert!(detect_role_changes(&before, &moved)
+        .iter()
+        .all(RoleChange::is_position));
+    assert!(!detect_role_changes(&before, &after)
+        .iter()
+        .all(RoleChange::is_position));
+
+    // Dangerous permissions are flagged.
+    assert_eq!(
+        describe_permissions(Permissions::MANAGE_WEBHOOKS, Permissions::ATTACH_FILES),
+        "+Manage Webhooks ⚠\n-Attach Files"
+    );
+    assert!(
+        describe_permissions(Permissions::all(), Permissions::empty())
+            .chars()
+            .count()
+            <= 1024
+    );
+}
+
+#[test]
+fn test_voice_sessions() {
+    use crate::event::voice::{detect_voice_changes, VoiceChange, VoiceSnapshot};
+    use crate::persistence::{end_voice_session, get_voice_sessions, start_voice_session};
+    use crate::util::discord::format_duration;
+    use chrono::{DateTime, TimeDelta};
+    use poise::serenity_prelude::ChannelId;
+
+    let (lounge, stage) = (Some(ChannelId::new(1)), Some(ChannelId::new(2)));
+    let away = VoiceSnapshot::default();
+    // Joining while server muted isn't a mute of its own.
+    let joined = VoiceSnapshot {
+        channel_id: lounge,
+        mute: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        detect_voice_changes(&away, &joined),
+        vec![VoiceChange::Joined(ChannelId::new(1))]
+    );
+    let streaming = VoiceSnapshot {
+        channel_id: stage,
+        mute: false,
+        deaf: true,
+        stream: true,
+        video: true,
+    };
+    assert_eq!(
+        detect_voice_changes(&joined, &streaming),
+        vec![
+            VoiceChange::Moved {
+                from: ChannelId::new(1),
+                to: ChannelId::new(2),
+            },
+            VoiceChange::ServerMuted(false),
+            VoiceChange::ServerDeafened(true),
+            VoiceChange::Stream(true),
+            VoiceChange::Video(true),
+        ]
+    );
+    assert_eq!(
+        detect_voice_changes(&streaming, &away),
+        vec![VoiceChange::Left(ChannelId::new(2))]
+    );
+    assert!(detect_voice_changes(&away, &away).is_empty());
+
+    let pool = test_pool();
+    let at = |secs| DateTime::from_timestamp(secs, 0).unwrap().naive_utc();
+    assert_eq!(end_voice_session(&pool, 1, 2, at(50)).unwrap(), None);
+    start_voice_session(&pool, 1, 2, 1, at(100)).unwrap();
+    assert_eq!(
+        end_voice_session(&pool, 1, 2, at(160)).unwrap(),
+        Some(at(100))
+    );
+    start_voice_session(&pool, 1, 2, 2, at(200)).unwrap();
+    start_voice_session(&pool, 9, 2, 3, at(300)).unwrap();
+    // A missed leave is closed by the next join.
+    start_voice_session(&pool, 1, 2, 1, at(400)).unwrap();
+
+    let sessions = get_voice_sessions(&pool, 1, 2).unwrap();
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions[0].channel_id, 1);
+    assert_eq!(sessions[0].left_at, None);
+    assert_eq!(sessions[1].channel_id, 2);
+    assert_eq!(sessions[1].left_at, Some(at(400)));
+    assert_eq!(sessions[2].left_at, Some(at(160)));
+    assert_eq!(get_voice_sessions(&pool, 9, 2).unwrap()[0].left_at, None);
+
+    assert_eq!(format_duration(TimeDelta::seconds(42)), "42s");
+    assert_eq!(format_duration(TimeDelta::seconds(125)), "2m 5s");
+    assert_eq!(format_duration(TimeDelta::seconds(7513)), "2h 5m 13s");
+}
+
+#[test]
+fn test_thread_changes() {
+    use crate::event::thread::{detect_thread_changes, ThreadChange, ThreadState};
+    use crate::persistence::models::Message;
+    use crate::persistence::{create_messages, get_channel_message_count, get_messages_by_channel};
+    use chrono::DateTime;
+    use poise::serenity_prelude::ForumTagId;
+
+    let tag = ForumTagId::new;
+    let before = ThreadState {
+        name: "help".to_string(),
+        archived: false,
+        locked: false,
+        tags: [tag(1), tag(2)].into(),
+    };
+    assert!(detect_thread_changes(&before, &before.clone()).is_empty());
+    let after = ThreadState {
+        name: "solved: help".to_string(),
+        archived: true,
+        locked: true,
+        tags: [tag(2), tag(3)].into(),
+    };
+    assert_eq!(
+        detect_thread_changes(&before, &after),
+        vec![
+            ThreadChange::Renamed {
+                before: "help".to_string(),
+                after: "solved: help".to_string(),
+            },
+            ThreadChange::Archived(true),
+            ThreadChange::Locked(true),
+            ThreadChange::Tags {
+                added: vec![tag(3)],
+                removed: vec![tag(1)],
+            },
+        ]
+    );
+
+    // Transcripts of deleted threads come from the messages stored for them.
+    let pool = test_pool();
+    let message = |id, channel, secs| Message {
+        id,
+        author: 2,
+        content: format!("message {}", id),
+        guild_id: Some(3),
+        channel_id: Some(channel),
+        created_at: DateTime::from_timestamp(secs, 0).unwrap().naive_utc(),
+        edited_at: None,
+        reply_to: None,
+        flags: 0,
+        pinned: false,
+    };
+    create_messages(
+        &pool,
+        &[
+            &message(1, 10, 300),
+            &message(2, 20, 100),
+            &message(3, 10, 200),
+        ],
+    )
+    .unwrap();
+    let ids: Vec<i64> = get_messages_by_channel(&pool, 10)
+        .unwrap()
+        .iter()
+        .map(|m| m.id)
+        .collect();
+    assert_eq!(ids, vec![3, 1]);
+    assert_eq!(get_channel_message_count(&pool, 20).unwrap(), 1);
+    assert!(get_messages_by_channel(&pool, 30).unwrap().is_empty());
+}
+
+#[test]
+fn test_server_changes() {
+    use crate::event::server::{
+        detect_guild_changes, diff_assets, Asset, AssetCache, AssetChange, GuildChange,
+        GuildSettings,
+    };
+    use poise::serenity_prelude::{ChannelId, GuildId, VerificationLevel};
+    use std::collections::BTreeMap;
+
+    let asset = |name: &str| Asset {
+        name: name.to_string(),
+        image: Some(format!("https://cdn.discordapp.com/emojis/{}.png", name)),
+    };
+    let before: BTreeMap<u64, Asset> = [(1, asset("kek")), (2, asset("pog"))].into();
+    let after: BTreeMap<u64, Asset> = [(2, asset("poggers")), (3, asset("wave"))].into();
+    assert!(diff_assets(&before, &before).is_empty());
+    assert_eq!(
+        diff_assets(&before, &after),
+        vec![
+            AssetChange::Renamed {
+                id: 2,
+                before: "pog".to_string(),
+                after: asset("poggers"),
+            },
+            AssetChange::Added(3, asset("wave")),
+            AssetChange::Removed(1, asset("kek")),
+        ]
+    );
+
+    // Without a snapshot from the guild's creation there is nothing to diff against.
+    let cache = AssetCache::default();
+    let guild = GuildId::new(1);
+    assert_eq!(cache.replace_emojis(guild, before.clone()), None);
+    assert_eq!(cache.replace_emojis(guild, after.clone()), Some(before));
+    assert_eq!(
+        cache.replace_stickers(guild, BTreeMap::new()),
+        Some(BTreeMap::new())
+    );
+
+    let before = GuildSettings {
+        name: "Kanshi".to_string(),
+        vanity_url: Some("kanshi".to_string()),
+        ..Default::default()
+    };
+    assert!(detect_guild_changes(&before, &before.clone()).is_empty());
+    let after = GuildSettings {
+        name: "Kanshi".to_string(),
+        icon: Some("a_hash".to_string()),
+        verification_level: VerificationLevel::High,
+        system_channel: Some(ChannelId::new(5)),
+        ..Default::default()
+    };
+    assert_eq!(
+        detect_guild_changes(&before, &after),
+        vec![
+            GuildChange::Icon {
+                before: None,
+                after: Some("a_hash".to_string()),
+            },
+            GuildChange::VerificationLevel {
+                before: VerificationLevel::None,
+                after: VerificationLevel::High,
+            },
+            GuildChange::SystemChannel {
+                before: None,
+                after: Some(ChannelId::new(5)),
+            },
+            GuildChange::VanityUrl {
+                before: Some("kanshi".to_string()),
+                after: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_invite_attribution() {
+    use crate::event::invite::{attribute_join, InviteCache, Invites, TrackedInvite};
+    use crate::persistence::get_top_inviters;
+    use crate::persistence::models::NewInviteUse;
+    use crate::persistence::record_invite_use;
+    use chrono::{DateTime, TimeDelta};
+    use poise::serenity_prelude::{GuildId, UserId};
+
+    let invite = |inviter: u64, uses: u64, max_uses: u64| TrackedInvite {
+        inviter: Some(UserId::new(inviter)),
+        uses,
+        max_uses,
+    };
+    let before: Invites = [
+        ("open".to_string(), invite(1, 4, 0)),
+        ("single".to_string(), invite(2, 0, 1)),
+        ("limited".to_string(), invite(2, 3, 10)),
+    ]
+    .into();
+
+    let none = Invites::new();
+    let mut used = before.clone();
+    used.get_mut("open").unwrap().uses = 5;
+    assert_eq!(
+        attribute_join(&before, &used, &none),
+        Some("open".to_string())
+    );
+
+    // Single-use invites are deleted once used up.
+    let mut exhausted = before.clone();
+    exhausted.remove("single");
+    assert_eq!(
+        attribute_join(&before, &exhausted, &none),
+        Some("single".to_string())
+    );
+    // Unless they reached their limit, vanished invites were revoked or expired.
+    let mut revoked = before.clone();
+    revoked.remove("limited");
+    assert_eq!(attribute_join(&before, &revoked, &none), None);
+
+    // Two joins in quick succession can't be told apart.
+    let mut ambiguous = used.clone();
+    ambiguous.get_mut("limited").unwrap().uses = 4;
+    assert_eq!(attribute_join(&before, &ambiguous, &none), None);
+    assert_eq!(attribute_join(&before, &before, &none), None);
+
+    // Discord may announce the deletion of a used up invite before the join.
+    let deleted: Invites = [("single".to_string(), invite(2, 0, 1))].into();
+    assert_eq!(
+        attribute_join(&exhausted, &exhausted, &deleted),
+        Some("single".to_string())
+    );
+    let revoked: Invites = [("limited".to_string(), invite(2, 3, 10))].into();
+    assert_eq!(attribute_join(&exhausted, &exhausted, &revoked), None);
+
+    let cache = InviteCache::default();
+    let guild = GuildId::new(7);
+    let at = |secs| {
+        DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .naive_utc()
+            + TimeDelta::seconds(secs)
+    };
+    cache.replace(guild, before.clone());
+    cache.remove(guild, "single", at(0));
+    assert_eq!(cache.recently_deleted(guild, at(10)), deleted);
+    cache.forget_deleted(guild, "single");
+    assert!(cache.recently_deleted(guild, at(10)).is_empty());
+    cache.replace(guild, before.clone());
+    cache.remove(guild, "single", at(0));
+    assert!(cache.recently_deleted(guild, at(60)).is_empty());
+
+    let pool = test_pool();
+    let at = DateTime::from_timestamp(1_700_000_000, 0)
+        .unwrap()
+        .naive_utc();
+    for (user, code, inviter) in [
+        (10, "open", Some(1)),
+        (11, "limited", Some(2)),
+        (12, "limited", Some(2)),
+        (13, "vanity", None),
+    ] {
+        let invite_use = NewInviteUse {
+            guild_id: 7,
+            user_id: user,
+            code: code.to_string(),
+            inviter_id: inviter,
+            used_at: at,
+        };
+        record_invite_use(&pool, invite_use).unwrap();
+    }
+    assert_eq!(
+        get_top_inviters(&pool, 7, 10).unwrap(),
+        vec![(2, 2), (1, 1)]
+    );
+    assert_eq!(get_top_inviters(&pool, 7, 1).unwrap(), vec![(2, 2)]);
+    assert!(get_top_inviters(&pool, 8, 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_pin_changes() {
+    use crate::event::pins::{detect_pin_changes, PinChange};
+    use crate::persistence::{get_channel_pins, save_channel_pins};
+    use std::collections::BTreeSet;
+
+    let before = BTreeSet::from([1, 2, 3]);
+    let after = BTreeSet::from([2, 3, 4]);
+    assert_eq!(
+        detect_pin_changes(&before, &after),
+        vec![PinChange::Pinned(4), PinChange::Unpinned(1)]
+    );
+    assert!(detect_pin_changes(&after, &after).is_empty());
+
+    let pool = test_pool();
+    assert_eq!(get_channel_pins(&pool, 5).unwrap(), None);
+    save_channel_pins(&pool, 5, &before).unwrap();
+    assert_eq!(get_channel_pins(&pool, 5).unwrap(), Some(before));
+    // A channel without pins is still known.
+    save_channel_pins(&pool, 5, &BTreeSet::new()).unwrap();
+    assert_eq!(get_channel_pins(&pool, 5).unwrap(), Some(BTreeSet::new()));
+}
+
+#[test]
+fn test_automod_rules() {
+    use crate::event::automod::{
+        detect_rule_changes, AutoModCache, ExecutionKey, RuleChange, RuleState,
+    };
+    use chrono::{DateTime, TimeDelta};
+    use poise::serenity_prelude::automod::{ActionExecution, Rule};
+    use poise::serenity_prelude::RoleId;
+
+    let rule = |keywords: &[&str], enabled: bool, actions: serde_json::Value| -> Rule {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "guild_id": "2",
+            "name": "Slurs",
+            "creator_id": "3",
+            "event_type": 1,
+            "trigger_type": 1,
+            "trigger_metadata": {
+                "keyword_filter": keywords,
+                "regex_patterns": [],
+                "allow_list": [],
+            },
+            "actions": actions,
+            "enabled": enabled,
+            "exempt_roles": [],
+            "exempt_channels": [],
+        }))
+        .unwrap()
+    };
+    let block = serde_json::json!([{ "type": 1, "metadata": {} }]);
+    let block_and_timeout = serde_json::json!([
+        { "type": 1, "metadata": {} },
+        { "type": 3, "metadata": { "duration_seconds": 600 } },
+    ]);
+
+    let before = RuleState::from_rule(&rule(&["foo", "bar"], true, block));
+    assert_eq!(before.actions, vec!["Block Message".to_string()]);
+    let mut after = RuleState::from_rule(&rule(&["bar", "baz"], false, block_and_timeout));
+    after.exempt_roles.insert(RoleId::new(4));
+    assert_eq!(
+        detect_rule_changes(&before, &after),
+        vec![
+            RuleChange::Enabled(false),
+            RuleChange::Keywords {
+                added: vec!["baz".to_string()],
+                removed: vec!["foo".to_string()],
+            },
+            RuleChange::Actions {
+                before: vec!["Block Message".to_string()],
+                after: vec![
+                    "Block Message".to_string(),
+                    "Timeout for 10m 0s".to_string()
+                ],
+            },
+            RuleChange::ExemptRoles {
+                added: vec![RoleId::new(4)],
+                removed: vec![],
+            },
+        ]
+    );
+    assert!(detect_rule_changes(&after, &after).is_empty());
+
+    let actions = [
+        serde_json::json!({ "type": 1, "metadata": {} }),
+        serde_json::json!({ "type": 2, "metadata": { "channel_id": "9" } }),
+        serde_json::json!({ "type": 3, "metadata": { "duration_seconds": 60 } }),
+    ];
+    let execution = |action: usize, rule: &str, message: Option<&str>, content: &str| {
+        let execution: ActionExecution = serde_json::from_value(serde_json::json!({
+            "guild_id": "2",
+            "action": actions[action - 1],
+            "rule_id": rule,
+            "rule_trigger_type": 1,
+            "user_id": "5",
+            "channel_id": "6",
+            "message_id": message,
+            "content": content,
+            "matched_keyword": "foo",
+        }))
+        .unwrap();
+        ExecutionKey::from(&execution)
+    };
+    // Discord sends an execution per action, which only count once without the rule cached.
+    let cache = AutoModCache::default();
+    let at = |secs| {
+        DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .naive_utc()
+            + TimeDelta::seconds(secs)
+    };
+    assert!(cache.first_execution(execution(1, "1", None, "foo"), at(0)));
+    assert!(!cache.first_execution(execution(2, "1", None, "foo"), at(1)));
+    assert!(cache.first_execution(execution(1, "7", None, "foo"), at(1)));
+    assert!(cache.first_execution(execution(1, "1", None, "foo bar"), at(1)));
+    assert!(cache.first_execution(execution(2, "1", Some("8"), "foo"), at(2)));
+    assert!(!cache.first_execution(execution(3, "1", Some("8"), "foo"), at(2)));
+    // A later trigger with the same content is a new one.
+    assert!(cache.first_execution(execution(1, "1", None, "foo"), at(30)));
+}
@@ -0,0 +1,79 @@
+use crate::persistence::models::Message;
+use std::collections::HashMap;
+
+fn author_name(names: &HashMap<i64, String>, author: i64) -> String {
+    names
+        .get(&author)
+        .cloned()
+        .unwrap_or_else(|| "<unknown user>".to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders stored messages as a plain-text transcript, one message per paragraph.
+pub fn plain_text(title: &str, messages: &[Message], names: &HashMap<i64, String>) -> String {
+    let mut transcript = format!("{}\n{}\n", title, "=".repeat(title.chars().count()));
+    for message in messages {
+        transcript.push_str(&format!(
+            "\n[{}] {} ({}){}\n{}\n",
+            message.created_at.format("%Y-%m-%d %H:%M:%S"),
+            author_name(names, message.author),
+            message.author,
+            if message.edited_at.is_some() {
+                " (edited)"
+            } else {
+                ""
+            },
+            message.content
+        ));
+    }
+    transcript
+}
+
+/// Renders stored messages as a self-contained HTML page.
+pub fn html(title: &str, messages: &[Message], names: &HashMap<i64, String>) -> String {
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>\
+         body {{ font-family: sans-serif; background: #313338; color: #dbdee1; }}\
+         .message {{ margin: 1em 0; }}\
+         .author {{ font-weight: bold; color: #f2f3f5; }}\
+         .meta {{ font-size: 0.8em; color: #949ba4; }}\
+         .content {{ white-space: pre-wrap; }}\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = escape_html(title)
+    );
+    for message in messages {
+        page.push_str(&format!(
+            "<div class=\"message\" id=\"{id}\">\n\
+             <span class=\"author\">{name}</span> \
+             <span class=\"meta\">{author} &middot; {created}{edited}</span>\n\
+             <div class=\"content\">{content}</div>\n</div>\n",
+            id = message.id,
+            name = escape_html(&author_name(names, message.author)),
+            author = message.author,
+            created = message.created_at.format("%Y-%m-%d %H:%M:%S"),
+            edited = if message.edited_at.is_some() {
+                " &middot; edited"
+            } else {
+                ""
+            },
+            content = escape_html(&message.content)
+        ));
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
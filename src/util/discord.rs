@@ -1,7 +1,117 @@
 use crate::error::DoomError::NotImplementedError;
 use crate::Error;
+use chrono::{NaiveDateTime, TimeDelta};
+use poise::serenity_prelude::Permissions;
 use std::vec::IntoIter;
+use unicode_segmentation::UnicodeSegmentation;
 
+/// Discord's limit on the length of embed field values, counted in characters.
+pub const FIELD_LIMIT: usize = 1024;
+
+/// Formats a UTC timestamp as a Discord timestamp tag, rendered in the reader's locale.
+pub fn format_timestamp(timestamp: NaiveDateTime) -> String {
+    format!("<t:{}:f>", timestamp.and_utc().timestamp())
+}
+
+/// Formats a UTC timestamp as a relative Discord timestamp tag, i.e. "3 days ago".
+pub fn format_relative(timestamp: NaiveDateTime) -> String {
+    format!("<t:{}:R>", timestamp.and_utc().timestamp())
+}
+
+/// Formats a duration down to the second, i.e. "2h 5m 13s".
+pub fn format_duration(duration: TimeDelta) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds),
+        (0, _) => format!("{}m {}s", minutes, seconds),
+        _ => format!("{}h {}m {}s", hours, minutes, seconds),
+    }
+}
+
+/// The display name of a single permission, i.e. "Manage Messages".
+pub fn permission_name(permission: Permissions) -> &'static str {
+    permission
+        .get_permission_names()
+        .first()
+        .copied()
+        .unwrap_or("Unknown Permission")
+}
+
+/// Shortens text to at most `limit` characters, marking the cut with an ellipsis.
+/// Unlike `String::truncate`, it neither panics within a multi-byte character nor splits up
+/// grapheme clusters, i.e. characters with combining marks or emoji sequences.
+pub fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let budget = limit.saturating_sub(1);
+    let mut end = 0;
+    let mut count = 0;
+    for (index, grapheme) in text.grapheme_indices(true) {
+        count += grapheme.chars().count();
+        if count > budget {
+            break;
+        }
+        end = index + grapheme.len();
+    }
+    format!("{}…", &text[..end])
+}
+
+/// Escapes the characters Discord would otherwise read as formatting.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes text and shortens it to at most `limit` characters. The raw text is cut, as a cut in
+/// the escaped text could separate a backslash from the character it escapes.
+pub fn truncate_escaped(text: &str, limit: usize) -> String {
+    let mut budget = limit;
+    loop {
+        let escaped = escape_markdown(&truncate(text, budget));
+        let overflow = escaped.chars().count().saturating_sub(limit);
+        if overflow == 0 || budget <= overflow {
+            return escaped;
+        }
+        budget -= overflow;
+    }
+}
+
+/// Joins as many items as fit into `limit` characters, noting how many were left out.
+pub fn join_limited(items: &[String], separator: &str, limit: usize) -> String {
+    let mut joined = String::new();
+    for (index, item) in items.iter().enumerate() {
+        let more = format!("... and {} more", items.len() - index);
+        let length = joined.chars().count() + separator.chars().count() + item.chars().count();
+        // Leave room for the note, unless this is the last item.
+        let reserved = if index + 1 == items.len() {
+            0
+        } else {
+            separator.chars().count() + more.chars().count() + 16
+        };
+        if length + reserved > limit {
+            if !joined.is_empty() {
+                joined.push_str(separator);
+            }
+            joined.push_str(&more);
+            break;
+        }
+        if index > 0 {
+            joined.push_str(separator);
+        }
+        joined.push_str(item);
+    }
+    joined
+}
+
+#[allow(clippy::unnecessary_unwrap)]
 pub fn limit_content_and_see_more<'a, I>(
     limit: usize,
     components: I,
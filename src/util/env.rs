@@ -0,0 +1,58 @@
+use crate::error::{DoomError, DoomResult};
+use std::env::VarError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+fn parse<T>(variable: &str, value: &str) -> DoomResult<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|why: T::Err| DoomError::ConfigError {
+            variable: variable.to_string(),
+            reason: why.to_string(),
+        })
+}
+
+/// Reads and parses an environment variable which has to be set.
+pub fn required_var<T>(variable: &str) -> DoomResult<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(variable) {
+        Ok(value) => parse(variable, &value),
+        Err(why) => Err(DoomError::ConfigError {
+            variable: variable.to_string(),
+            reason: why.to_string(),
+        }),
+    }
+}
+
+/// Reads and parses an environment variable, which may be left unset.
+pub fn optional_var<T>(variable: &str) -> DoomResult<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(variable) {
+        Ok(value) => parse(variable, &value).map(Some),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(why) => Err(DoomError::ConfigError {
+            variable: variable.to_string(),
+            reason: why.to_string(),
+        }),
+    }
+}
+
+/// Reads and parses an environment variable, falling back to `default` if it isn't set.
+pub fn var_or<T>(variable: &str, default: T) -> DoomResult<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    Ok(optional_var(variable)?.unwrap_or(default))
+}
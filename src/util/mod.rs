@@ -1,3 +1,4 @@
 pub mod discord;
+pub mod env;
 
 pub const UNKNOWN_USER: u64 = 0;